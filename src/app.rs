@@ -1,31 +1,148 @@
 use ratatui::{
     crossterm::{
-        terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+        cursor::{Hide, Show},
+        event,
+        terminal::{
+            disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+        },
         ExecutableCommand,
     },
     prelude::*,
     widgets::*,
 };
 use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
     io::{stdout, Result},
-    process::{Command, Stdio},
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
-mod fuzzy;
-mod parser;
+pub mod backup;
+pub mod cst;
+pub mod diff;
+pub mod editor;
+pub mod fuzzy;
+pub mod history;
+pub mod launcher;
+pub mod locations;
+pub mod parser;
+mod paths;
+mod pattern;
+pub mod query;
+pub mod resolve;
+pub mod safety;
+pub mod settings;
+pub mod state;
+
+/// Splits `text` into spans, styling the characters at `positions` (char
+/// indices, ascending) with `matched` and the rest with `base`.
+fn highlight_matches(
+    text: &str,
+    positions: &[usize],
+    base: Style,
+    matched: Style,
+) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    let mut next = positions.iter().peekable();
+
+    for (index, c) in text.chars().enumerate() {
+        let is_match = next.next_if_eq(&&index).is_some();
+        if is_match != run_matched && !run.is_empty() {
+            let style = if run_matched { matched } else { base };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        run_matched = is_match;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        let style = if run_matched { matched } else { base };
+        spans.push(Span::styled(run, style));
+    }
+    spans
+}
+
+/// Hands the terminal back to its normal state for as long as it lives, so a
+/// foreground program can use it, and restores the TUI's raw mode and
+/// alternate screen when dropped, even if the program failed to start.
+struct SuspendedTerminal;
+
+impl SuspendedTerminal {
+    fn new() -> Result<Self> {
+        disable_raw_mode()?;
+        stdout().execute(LeaveAlternateScreen)?.execute(Show)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for SuspendedTerminal {
+    fn drop(&mut self) {
+        let _ = stdout().execute(EnterAlternateScreen).and_then(|out| out.execute(Hide));
+        let _ = enable_raw_mode();
+        // Keys typed while the session was closing must not act on the TUI.
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            if event::read().is_err() {
+                break;
+            }
+        }
+    }
+}
 
 #[derive(PartialEq, Clone)]
 pub enum AppMode {
     FileSelection,
     HostSelection,
     Search,
+    /// Searching the hosts of every config file at once.
+    GlobalSearch,
+    /// Browsing past connection attempts.
+    History,
+    /// Browsing the backups taken of config files before they were written.
+    Backups,
+}
+
+impl AppMode {
+    pub fn lists_hosts(&self) -> bool {
+        matches!(
+            self,
+            AppMode::HostSelection | AppMode::Search | AppMode::GlobalSearch
+        )
+    }
+}
+
+/// A row of the host list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostRow {
+    /// Heading of a `# ==== Title ====` section, with the number of listed
+    /// hosts in it.
+    Group { title: String, hosts: usize },
+    /// Position in `filtered_hosts`.
+    Host(usize),
 }
 
 pub struct App {
     pub files: Vec<parser::FileEntry>,
     pub filtered_files: Vec<(usize, parser::FileEntry)>,
-    pub hosts: Vec<Line<'static>>,
-    pub filtered_hosts: Vec<(usize, Line<'static>)>,
+    /// Matched character positions in each filtered file name, by index into
+    /// `files`.
+    pub file_matches: HashMap<usize, Vec<usize>>,
+    pub hosts: Vec<parser::SshHost>,
+    pub filtered_hosts: Vec<(usize, parser::SshHost)>,
+    /// The field each filtered host matched the search query on, by index
+    /// into `hosts`.
+    pub host_matches: HashMap<usize, fuzzy::FieldMatch>,
+    /// What the host list shows: `filtered_hosts`, under section headings
+    /// when browsing a file whose hosts are grouped.
+    pub host_rows: Vec<HostRow>,
+    /// Titles of the sections whose hosts are hidden.
+    pub collapsed_groups: HashSet<String>,
+    /// Only hosts with this tag are listed.
+    pub tag_filter: Option<String>,
+    /// Host and Match blocks of each loaded config file, used to compute the
+    /// effective configuration of the selected host.
+    pub config_blocks: HashMap<PathBuf, Vec<resolve::Block>>,
     pub selected: usize,
     pub current_file: Option<parser::FileEntry>,
     pub status_message: String,
@@ -33,16 +150,77 @@ pub struct App {
     pub state: ListState,
     pub mode: AppMode,
     pub search_query: String,
-    #[allow(dead_code)]
-    pub config_folder: String,
+    pub locations: locations::ConfigLocations,
+    pub settings: settings::Settings,
+    /// Past connections, used to rank hosts by frecency.
+    pub history: history::History,
+    /// Indices into `history.visits()` of the entries listed in the history
+    /// view, newest first.
+    pub filtered_history: Vec<usize>,
+    /// Matched character positions in the alias of each listed history entry,
+    /// by index into `history.visits()`.
+    pub history_matches: HashMap<usize, Vec<usize>>,
+    /// The mode to go back to when leaving the history or backups view.
+    view_return: AppMode,
+    /// Favourites and other state kept between runs.
+    pub saved_state: state::State,
+    /// Pending connection to a production host, shown as a dialog.
+    pub confirmation: Option<safety::Confirmation>,
+    /// The host form, while adding or editing a host.
+    pub form: Option<editor::HostForm>,
+    /// Host waiting for its deletion to be confirmed.
+    pub deletion: Option<parser::SshHost>,
+    /// Host being moved or copied, while its destination file is picked.
+    pub transfer: Option<editor::Transfer>,
+    /// Host being renamed, while its new alias is typed.
+    pub renaming: Option<editor::Rename>,
+    /// Hosts marked for a bulk edit, by alias and the file declaring them.
+    marked: Vec<(String, PathBuf)>,
+    /// Option being set on the marked hosts, and then its preview.
+    pub bulk: Option<editor::BulkEdit>,
+    /// Copies of config files taken before each write.
+    pub backups: backup::Backups,
+    /// Edits to config files made in this session.
+    edits: backup::UndoStack,
 }
 
 impl App {
-    pub fn new() -> App {
-        let config_folder = "c:/users/xpvm5843/.ssh/config.d".to_string(); // Default folder
-        let files = parser::get_files_in_folder(&config_folder);
+    pub fn new(locations: locations::ConfigLocations, settings: settings::Settings) -> App {
+        let mut warnings = String::new();
+        let history = history::History::load().unwrap_or_else(|e| {
+            warnings.push_str(&format!("\nCould not read connection history: {}", e));
+            history::History::default()
+        });
+        let backups = backup::Backups::load().unwrap_or_else(|e| {
+            warnings.push_str(&format!("\nCould not read config backups: {}", e));
+            backup::Backups::default()
+        });
+        let saved_state = state::State::load().unwrap_or_else(|e| {
+            warnings.push_str(&format!("\nCould not read saved state: {}", e));
+            state::State::default()
+        });
+        let mut app = App::with_data(locations, settings, history, saved_state, backups);
+        app.status_message.push_str(&warnings);
+        app
+    }
+
+    /// An app keeping its connection history, state and backups in the ones
+    /// given rather than in the data directory.
+    pub fn with_data(
+        locations: locations::ConfigLocations,
+        settings: settings::Settings,
+        history: history::History,
+        saved_state: state::State,
+        backups: backup::Backups,
+    ) -> App {
+        let files = locations.discover_files();
         let number_of_files = files.len();
-        
+        let status_message = format!(
+            "Loaded {} files from {}",
+            number_of_files,
+            locations.describe()
+        );
+
         let filtered_files: Vec<(usize, parser::FileEntry)> = files
             .iter()
             .enumerate()
@@ -52,46 +230,785 @@ impl App {
         App {
             files,
             filtered_files,
+            file_matches: HashMap::new(),
             hosts: Vec::new(),
             filtered_hosts: Vec::new(),
+            host_matches: HashMap::new(),
+            host_rows: Vec::new(),
+            collapsed_groups: HashSet::new(),
+            tag_filter: None,
+            config_blocks: HashMap::new(),
             selected: 0,
             current_file: None,
-            status_message: format!("Loaded {} files from {}", number_of_files, config_folder),
+            status_message,
             vertical_scroll_state: ScrollbarState::new(number_of_files),
             state: ListState::default().with_selected(Some(0)),
             mode: AppMode::FileSelection,
             search_query: String::new(),
-            config_folder,
+            locations,
+            settings,
+            history,
+            filtered_history: Vec::new(),
+            history_matches: HashMap::new(),
+            view_return: AppMode::FileSelection,
+            saved_state,
+            confirmation: None,
+            form: None,
+            deletion: None,
+            transfer: None,
+            renaming: None,
+            marked: Vec::new(),
+            bulk: None,
+            backups,
+            edits: backup::UndoStack::default(),
         }
     }
 
+    /// Whether the Favourites pseudo-file is listed above the config files,
+    /// which it is while there are favourites and the list is not filtered.
+    pub fn lists_favourites(&self) -> bool {
+        self.mode == AppMode::FileSelection
+            && self.search_query.is_empty()
+            && !self.saved_state.favourites.is_empty()
+    }
+
+    /// Number of rows in the file list, counting the Favourites pseudo-file.
+    pub fn file_rows(&self) -> usize {
+        self.filtered_files.len() + usize::from(self.lists_favourites())
+    }
+
     pub fn load_hosts_from_selected_file(&mut self) {
-        if self.filtered_files.is_empty() {
-            self.status_message = "No file selected".to_string();
+        if self.lists_favourites() && self.selected == 0 {
+            self.load_favourites();
             return;
         }
+        let index = self.selected - usize::from(self.lists_favourites());
+        let Some((_, selected_file)) = self.filtered_files.get(index) else {
+            self.status_message = "No file selected".to_string();
+            return;
+        };
+
+        let selected_file = selected_file.clone();
+        self.load_hosts(selected_file);
+    }
 
-        let selected_file = &self.filtered_files[self.selected].1;
-        self.current_file = Some(selected_file.clone());
+    fn load_hosts(&mut self, file: parser::FileEntry) {
+        self.current_file = Some(file.clone());
         
-        self.hosts = parser::parse_ssh_hosts(&selected_file.path);
-        self.filtered_hosts = self.hosts
-            .iter()
-            .enumerate()
-            .map(|(i, item)| (i, item.clone()))
-            .collect();
+        let (hosts, warnings) = parser::parse_ssh_hosts(&file.path, &paths::ssh_dir());
+        self.hosts = hosts;
+        self.config_blocks = HashMap::from([(
+            file.path.clone(),
+            resolve::read_blocks(&file.path, &paths::ssh_dir()).0,
+        )]);
+        self.mode = AppMode::HostSelection;
+        self.search_query.clear();
+        self.update_filtered_hosts();
 
         let number_of_hosts = self.hosts.len();
-        self.vertical_scroll_state = ScrollbarState::new(number_of_hosts);
         self.selected = 0;
         self.state.select(Some(0));
-        self.mode = AppMode::HostSelection;
         
+        self.status_message = format!("Loaded {} hosts from {}", number_of_hosts, file.name);
+        for warning in warnings {
+            self.status_message.push('\n');
+            self.status_message.push_str(&warning);
+        }
+    }
+
+    /// Reads the listed hosts again after their files changed, selecting
+    /// `alias` if it is still listed.
+    fn reload_hosts(&mut self, alias: &str) {
+        let Some(file) = self.current_file.clone() else {
+            return;
+        };
+        // The Favourites pseudo-file has no path.
+        if file.path.as_os_str().is_empty() {
+            self.load_favourites();
+        } else {
+            self.load_hosts(file);
+        }
+        let row = self.host_rows.iter().position(|row| {
+            matches!(row, HostRow::Host(position)
+                if self.filtered_hosts[*position].1.alias == alias)
+        });
+        if let Some(row) = row {
+            self.selected = row;
+            self.state.select(Some(row));
+        }
+    }
+
+    /// Opens the host form for a new host, written to the listed file by
+    /// default.
+    pub fn open_new_host_form(&mut self) {
+        let files: Vec<PathBuf> = self.files.iter().map(|file| file.path.clone()).collect();
+        let current = self.current_file.as_ref().map(|file| file.path.clone());
+        let Some(file) = current.filter(|path| files.contains(path)).or(files.first().cloned())
+        else {
+            self.status_message = "No config file to add a host to".to_string();
+            return;
+        };
+        self.form = Some(editor::HostForm::new_host(files, file));
+    }
+
+    /// Opens the host form on the selected host's block.
+    pub fn open_edit_form(&mut self) {
+        let Some(host) = self.selected_host().cloned() else {
+            return;
+        };
+        let form = cst::ConfigFile::read(&host.source_file)
+            .map_err(|e| format!("Cannot read {}: {}", host.source_file.display(), e))
+            .and_then(|config| editor::HostForm::edit_host(&host, &config));
+        match form {
+            Ok(form) => self.form = Some(form),
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    pub fn cancel_form(&mut self) {
+        self.form = None;
+    }
+
+    /// Writes the form to its file and reloads the hosts. The form stays open
+    /// when it cannot be saved.
+    pub fn save_form(&mut self) {
+        let Some(form) = self.form.take() else {
+            return;
+        };
+        let alias = form.alias().to_string();
+        let description = match &form.editing {
+            Some(host) => format!("Edit {}", host.alias),
+            None => format!("Add {}", alias),
+        };
+        let saved = cst::ConfigFile::read(&form.file)
+            .map_err(|e| format!("Cannot read {}: {}", form.file.display(), e))
+            .and_then(|mut config| {
+                form.apply(&mut config)?;
+                let mut files = vec![(form.file.clone(), config)];
+                if let Some(host) = form.editing.as_ref().filter(|host| host.alias != alias) {
+                    self.check_new_alias(&alias)?;
+                    self.update_references(&host.alias, &alias, &mut files)?;
+                }
+                self.write_configs(description, files)
+            });
+        if let Err(e) = saved {
+            self.status_message = e;
+            self.form = Some(form);
+            return;
+        }
+
+        let warning = form.editing.as_ref().and_then(|host| {
+            let renamed = state::HostRef {
+                alias: alias.clone(),
+                config_file: host.config_file.clone(),
+            };
+            self.follow_favourite(host, renamed)
+        });
+        self.reload_hosts(&alias);
+        let verb = if form.editing.is_some() { "Saved" } else { "Added" };
+        self.status_message = format!("{} {} in {}", verb, alias, form.file.display());
+        self.status_message.extend(warning);
+        let shared = form.editing.as_ref().is_some_and(|host| {
+            self.hosts.iter().any(|other| {
+                other.alias != alias
+                    && other.source_file == host.source_file
+                    && other.line == host.line
+            })
+        });
+        if shared {
+            self.status_message
+                .push_str("\nIts Host line declares other hosts, which share these options");
+        }
+    }
+
+    /// Writes edited config files, backing up what they replace, and records
+    /// the edit so it can be undone.
+    fn write_configs(
+        &mut self,
+        description: String,
+        files: Vec<(PathBuf, cst::ConfigFile)>,
+    ) -> std::result::Result<(), String> {
+        let mut changes = Vec::new();
+        for (path, config) in files {
+            let before = backup::read_existing(&path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            let after = Some(config.render());
+            if before != after {
+                changes.push(backup::FileChange {
+                    path,
+                    before,
+                    after,
+                });
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let edit = backup::Edit {
+            description,
+            changes,
+        };
+        edit.apply(&mut self.backups)?;
+        self.edits.record(edit);
+        Ok(())
+    }
+
+    /// Reverts the last edit made in this session.
+    pub fn undo(&mut self) {
+        let result = self.edits.undo(&mut self.backups);
+        self.finish_undo(result, "Undid", "Nothing to undo");
+    }
+
+    /// Makes the last undone edit again.
+    pub fn redo(&mut self) {
+        let result = self.edits.redo(&mut self.backups);
+        self.finish_undo(result, "Redid", "Nothing to redo");
+    }
+
+    fn finish_undo(
+        &mut self,
+        result: Option<std::result::Result<String, String>>,
+        verb: &str,
+        nothing: &str,
+    ) {
+        let message = match result {
+            None => nothing.to_string(),
+            Some(Err(e)) => e,
+            Some(Ok(description)) => {
+                self.refresh_after_write();
+                format!("{}: {}", verb, description)
+            }
+        };
+        self.status_message = message;
+    }
+
+    /// Shows the config files as they are after a write.
+    fn refresh_after_write(&mut self) {
+        match self.mode {
+            AppMode::HostSelection => {
+                let alias = self.selected_host().map(|host| host.alias.clone());
+                self.reload_hosts(&alias.unwrap_or_default());
+            }
+            AppMode::Backups => {
+                let content_length = self.backups.entries().len();
+                self.vertical_scroll_state =
+                    self.vertical_scroll_state.content_length(content_length);
+            }
+            _ => {}
+        }
+    }
+
+    /// Opens the list of backups from the file or host list.
+    pub fn enter_backups(&mut self) {
+        if !matches!(self.mode, AppMode::FileSelection | AppMode::HostSelection) {
+            return;
+        }
+        self.view_return = self.mode.clone();
+        self.mode = AppMode::Backups;
+        self.selected = 0;
+        self.state.select(Some(0));
+        self.refresh_after_write();
+    }
+
+    pub fn exit_backups(&mut self) {
+        self.mode = self.view_return.clone();
+        self.selected = 0;
+        self.state.select(Some(0));
+        if self.mode == AppMode::HostSelection {
+            self.refresh_after_write();
+        } else {
+            self.update_filtered_files();
+        }
+    }
+
+    /// The backup at the selected row; backups are listed newest first.
+    pub fn selected_backup(&self) -> Option<&backup::Backup> {
+        if self.mode != AppMode::Backups {
+            return None;
+        }
+        self.backups.entries().iter().rev().nth(self.selected)
+    }
+
+    /// Writes the selected backup back to its config file. The content it
+    /// replaces is backed up too, and the restore can be undone.
+    pub fn restore_backup(&mut self) {
+        let Some(backup) = self.selected_backup().cloned() else {
+            return;
+        };
+        let when = history::format_timestamp(backup.timestamp);
+        let restored = self
+            .backups
+            .read(&backup)
+            .map_err(|e| format!("Cannot read the backup {}: {}", backup.name, e))
+            .and_then(|content| {
+                let description = format!("Restore {} from {}", backup.original.display(), when);
+                let config = cst::ConfigFile::parse(&content);
+                self.write_configs(description, vec![(backup.original.clone(), config)])
+            });
+        self.status_message = match restored {
+            Ok(()) => format!(
+                "Restored {} from {} UTC",
+                backup.original.display(),
+                when
+            ),
+            Err(e) => e,
+        };
+        self.selected = 0;
+        self.state.select(Some(0));
+        self.refresh_after_write();
+    }
+
+    /// Asks to confirm deleting the selected host.
+    pub fn request_deletion(&mut self) {
+        self.deletion = self.selected_host().cloned();
+    }
+
+    pub fn cancel_deletion(&mut self) {
+        self.deletion = None;
+    }
+
+    /// Removes the host waiting for confirmation from its file.
+    pub fn delete_host(&mut self) {
+        let Some(host) = self.deletion.take() else {
+            return;
+        };
+        let file = &host.source_file;
+        let deleted = cst::ConfigFile::read(file)
+            .map_err(|e| format!("Cannot read {}: {}", file.display(), e))
+            .and_then(|mut config| {
+                editor::delete_host(&mut config, &host)?;
+                let description = format!("Delete {}", host.alias);
+                self.write_configs(description, vec![(file.clone(), config)])
+            });
+        match deleted {
+            Ok(()) => {
+                self.reload_hosts("");
+                self.status_message = format!("Deleted {} from {}", host.alias, file.display());
+            }
+            Err(e) => self.status_message = e,
+        }
+    }
+
+    /// Opens the list of files the selected host can be moved or copied to.
+    pub fn open_transfer(&mut self, copy: bool) {
+        let Some(host) = self.selected_host().cloned() else {
+            return;
+        };
+        let files: Vec<PathBuf> = self
+            .files
+            .iter()
+            .map(|file| file.path.clone())
+            .filter(|path| *path != host.source_file)
+            .collect();
+        if files.is_empty() {
+            self.status_message = format!("No other config file to put {} in", host.alias);
+            return;
+        }
+        self.transfer = Some(editor::Transfer {
+            host,
+            copy,
+            files,
+            selected: 0,
+        });
+    }
+
+    pub fn cancel_transfer(&mut self) {
+        self.transfer = None;
+    }
+
+    /// Adds the host's block to the picked file and, for a move, removes it
+    /// from its own file, as a single edit.
+    pub fn confirm_transfer(&mut self) {
+        let Some(transfer) = self.transfer.take() else {
+            return;
+        };
+        let Some(destination) = transfer.files.get(transfer.selected).cloned() else {
+            return;
+        };
+        let host = &transfer.host;
+        let read = |path: &PathBuf| {
+            cst::ConfigFile::read(path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+        };
+        let verb = if transfer.copy { "Copy" } else { "Move" };
+        let transferred = read(&host.source_file).and_then(|mut source| {
+            let nodes = editor::block_nodes(&source, host)?;
+            let mut target = read(&destination)?;
+            editor::append_block(&mut target, &destination, &host.alias, nodes)?;
+            let mut files = vec![(destination.clone(), target)];
+            if !transfer.copy {
+                editor::delete_host(&mut source, host)?;
+                files.push((host.source_file.clone(), source));
+            }
+            let description = format!("{} {} to {}", verb, host.alias, destination.display());
+            self.write_configs(description, files)
+        });
+        if let Err(e) = transferred {
+            self.status_message = e;
+            return;
+        }
+
+        let mut warning = None;
+        if !transfer.copy {
+            let moved = state::HostRef {
+                alias: host.alias.clone(),
+                config_file: destination.clone(),
+            };
+            warning = self.follow_favourite(host, moved);
+        }
+        self.reload_hosts(&host.alias);
+        let verb = if transfer.copy { "Copied" } else { "Moved" };
+        self.status_message = format!("{} {} to {}", verb, host.alias, destination.display());
+        self.status_message.extend(warning);
+    }
+
+    /// Asks for a new alias for the selected host.
+    pub fn open_rename(&mut self) {
+        if let Some(host) = self.selected_host().cloned() {
+            let input = host.alias.clone();
+            self.renaming = Some(editor::Rename { host, input });
+        }
+    }
+
+    pub fn type_rename(&mut self, c: char) {
+        if let Some(rename) = &mut self.renaming {
+            rename.input.push(c);
+        }
+    }
+
+    pub fn erase_rename(&mut self) {
+        if let Some(rename) = &mut self.renaming {
+            rename.input.pop();
+        }
+    }
+
+    pub fn cancel_rename(&mut self) {
+        self.renaming = None;
+    }
+
+    /// Renames the host and every `ProxyJump` and `ProxyCommand` naming it, as
+    /// a single edit. The prompt stays open when the alias cannot be used.
+    pub fn confirm_rename(&mut self) {
+        let Some(rename) = self.renaming.take() else {
+            return;
+        };
+        let host = &rename.host;
+        let alias = rename.input.trim().to_string();
+        if alias == host.alias {
+            return;
+        }
+        let file = &host.source_file;
+        let renamed = cst::ConfigFile::read(file)
+            .map_err(|e| format!("Cannot read {}: {}", file.display(), e))
+            .and_then(|mut config| {
+                self.check_new_alias(&alias)?;
+                editor::rename_host(&mut config, host, &alias)?;
+                let mut files = vec![(file.clone(), config)];
+                let references = self.update_references(&host.alias, &alias, &mut files)?;
+                let description = format!("Rename {} to {}", host.alias, alias);
+                self.write_configs(description, files)?;
+                Ok(references)
+            });
+        let references = match renamed {
+            Ok(references) => references,
+            Err(e) => {
+                self.status_message = e;
+                self.renaming = Some(rename);
+                return;
+            }
+        };
+
+        let new = state::HostRef {
+            alias: alias.clone(),
+            config_file: host.config_file.clone(),
+        };
+        let warning = self.follow_favourite(host, new);
+        self.reload_hosts(&alias);
         self.status_message = format!(
-            "Loaded {} hosts from {}",
-            number_of_hosts,
-            selected_file.name
+            "Renamed {} to {}, updating {} reference(s)",
+            host.alias, alias, references
         );
+        self.status_message.extend(warning);
+    }
+
+    pub fn is_marked(&self, host: &parser::SshHost) -> bool {
+        self.marked
+            .iter()
+            .any(|(alias, file)| *alias == host.alias && *file == host.source_file)
+    }
+
+    /// Marks or unmarks the selected host for a bulk edit and moves to the
+    /// next row.
+    pub fn toggle_mark(&mut self) {
+        let Some(host) = self.selected_host().cloned() else {
+            return;
+        };
+        if self.is_marked(&host) {
+            self.marked
+                .retain(|(alias, file)| *alias != host.alias || *file != host.source_file);
+        } else {
+            self.marked
+                .push((host.alias.clone(), host.source_file.clone()));
+        }
+        self.status_message = format!("{} host(s) marked", self.marked.len());
+        self.move_down();
+    }
+
+    /// Opens the bulk edit on the marked hosts, or on the selected host when
+    /// none is marked.
+    pub fn open_bulk_edit(&mut self) {
+        let marked: Vec<parser::SshHost> = self
+            .hosts
+            .iter()
+            .filter(|host| self.is_marked(host))
+            .cloned()
+            .collect();
+        let hosts = if marked.is_empty() {
+            self.selected_host().cloned().into_iter().collect()
+        } else {
+            marked
+        };
+        if !hosts.is_empty() {
+            self.bulk = Some(editor::BulkEdit::new(hosts));
+        }
+    }
+
+    /// Shows what the bulk edit changes in each file, or the reason it
+    /// cannot be made.
+    pub fn preview_bulk_edit(&mut self) {
+        if let Some(bulk) = &mut self.bulk {
+            if let Err(e) = bulk.make_preview() {
+                self.status_message = e;
+            }
+        }
+    }
+
+    /// Leaves the preview for the option, or the option for the host list.
+    pub fn cancel_bulk_edit(&mut self) {
+        match &mut self.bulk {
+            Some(bulk) if bulk.preview.is_some() => bulk.preview = None,
+            _ => self.bulk = None,
+        }
+    }
+
+    /// Writes the previewed changes as a single edit, unless a file changed
+    /// since the preview.
+    pub fn apply_bulk_edit(&mut self) {
+        let Some(bulk) = self.bulk.take() else {
+            return;
+        };
+        let Some(previews) = &bulk.preview else {
+            self.bulk = Some(bulk);
+            return;
+        };
+        let mut files = Vec::new();
+        for preview in previews {
+            let current = backup::read_existing(&preview.path);
+            if !current.is_ok_and(|current| current == preview.before) {
+                self.status_message = format!(
+                    "{} changed since the preview, nothing was written",
+                    preview.path.display()
+                );
+                return;
+            }
+            files.push((preview.path.clone(), preview.after.clone()));
+        }
+        if let Err(e) = self.write_configs(bulk.description(), files) {
+            self.status_message = e;
+            return;
+        }
+
+        self.marked.clear();
+        let alias = self.selected_host().map(|host| host.alias.clone());
+        self.reload_hosts(&alias.unwrap_or_default());
+        self.status_message = format!("{} in {} file(s)", bulk.description(), previews.len());
+    }
+
+    /// Points the `ProxyJump` and `ProxyCommand` references to `old` at `new`
+    /// in `files` and in every other config file known, adding the files that
+    /// change to `files`. Returns how many lines changed.
+    fn update_references(
+        &self,
+        old: &str,
+        new: &str,
+        files: &mut Vec<(PathBuf, cst::ConfigFile)>,
+    ) -> std::result::Result<usize, String> {
+        let mut references = 0;
+        for (_, config) in files.iter_mut() {
+            references += editor::rename_references(config, old, new);
+        }
+        let (known, _) = self.known_configs();
+        for path in known {
+            if files.iter().any(|(written, _)| *written == path) {
+                continue;
+            }
+            let mut config = cst::ConfigFile::read(&path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            let changed = editor::rename_references(&mut config, old, new);
+            if changed > 0 {
+                references += changed;
+                files.push((path, config));
+            }
+        }
+        Ok(references)
+    }
+
+    /// Every config file listed and the files they include, with the host
+    /// aliases declared in them and where.
+    fn known_configs(&self) -> (Vec<PathBuf>, Vec<(String, PathBuf)>) {
+        let ssh_dir = paths::ssh_dir();
+        let mut files: Vec<PathBuf> = Vec::new();
+        let mut aliases = Vec::new();
+        for file in &self.files {
+            parser::walk_config(&file.path, &ssh_dir, &mut |event| {
+                if let parser::ConfigEvent::Directive(directive) = event {
+                    if !files.contains(&directive.file) {
+                        files.push(directive.file.clone());
+                    }
+                    if directive.is("host") {
+                        for alias in &directive.args {
+                            aliases.push((alias.clone(), directive.file.clone()));
+                        }
+                    }
+                }
+            });
+            if !files.contains(&file.path) {
+                files.push(file.path.clone());
+            }
+        }
+        for host in &self.hosts {
+            if !files.contains(&host.source_file) {
+                files.push(host.source_file.clone());
+            }
+            aliases.push((host.alias.clone(), host.source_file.clone()));
+        }
+        (files, aliases)
+    }
+
+    /// Fails when a host is renamed to an alias declared anywhere, as one of
+    /// the two declarations would shadow the other.
+    fn check_new_alias(&self, alias: &str) -> std::result::Result<(), String> {
+        let (_, aliases) = self.known_configs();
+        match aliases.iter().find(|(declared, _)| declared == alias) {
+            Some((_, file)) => Err(format!(
+                "{} is already declared in {}",
+                alias,
+                file.display()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps `host` starred once it was renamed or moved to `new`.
+    fn follow_favourite(&mut self, host: &parser::SshHost, new: state::HostRef) -> Option<String> {
+        let saved = self.saved_state.replace_favourite(host, new);
+        saved
+            .err()
+            .map(|e| format!("\nCould not save favourites: {}", e))
+    }
+
+    /// Lists the starred hosts, looked up in the config files they were
+    /// starred from.
+    pub fn load_favourites(&mut self) {
+        let ssh_dir = paths::ssh_dir();
+        let mut parsed: HashMap<PathBuf, Vec<parser::SshHost>> = HashMap::new();
+        let mut warnings = Vec::new();
+        self.hosts.clear();
+        self.config_blocks.clear();
+
+        for favourite in &self.saved_state.favourites {
+            let file = &favourite.config_file;
+            let hosts = parsed.entry(file.clone()).or_insert_with(|| {
+                let (hosts, file_warnings) = parser::parse_ssh_hosts(file, &ssh_dir);
+                warnings.extend(file_warnings);
+                hosts
+            });
+            match hosts.iter().find(|host| host.alias == favourite.alias) {
+                Some(host) => self.hosts.push(host.clone()),
+                None => warnings.push(format!(
+                    "Favourite {} is no longer in {}",
+                    favourite.alias,
+                    file.display()
+                )),
+            }
+            if !self.config_blocks.contains_key(file) {
+                let blocks = resolve::read_blocks(file, &ssh_dir).0;
+                self.config_blocks.insert(file.clone(), blocks);
+            }
+        }
+
+        self.current_file = Some(parser::FileEntry::new(
+            "Favourites".to_string(),
+            PathBuf::new(),
+        ));
+        self.mode = AppMode::HostSelection;
+        self.search_query.clear();
+        self.update_filtered_hosts();
+        self.selected = 0;
+        self.state.select(Some(0));
+        self.status_message = format!("Loaded {} favourites", self.hosts.len());
+        for warning in warnings {
+            self.status_message.push('\n');
+            self.status_message.push_str(&warning);
+        }
+    }
+
+    /// Stars or unstars the selected host.
+    pub fn toggle_favourite(&mut self) {
+        let Some(host) = self.selected_host().cloned() else {
+            return;
+        };
+        self.status_message = match self.saved_state.toggle_favourite(&host) {
+            Ok(true) => format!("Added {} to favourites", host.alias),
+            Ok(false) => format!("Removed {} from favourites", host.alias),
+            Err(e) => format!("Could not save favourites: {}", e),
+        };
+    }
+
+    /// Loads the hosts of every config file so they can be searched together.
+    /// A host reached from several files through `Include` is listed once,
+    /// under the first file that reaches it.
+    pub fn enter_global_search(&mut self) {
+        let ssh_dir = paths::ssh_dir();
+        self.hosts.clear();
+        self.config_blocks.clear();
+        let mut warnings = Vec::new();
+
+        for file in &self.files {
+            let (hosts, file_warnings) = parser::parse_ssh_hosts(&file.path, &ssh_dir);
+            for host in hosts {
+                let seen = self.hosts.iter().any(|known| {
+                    known.alias == host.alias
+                        && known.source_file == host.source_file
+                        && known.line == host.line
+                });
+                if !seen {
+                    self.hosts.push(host);
+                }
+            }
+            warnings.extend(file_warnings);
+            self.config_blocks.insert(
+                file.path.clone(),
+                resolve::read_blocks(&file.path, &ssh_dir).0,
+            );
+        }
+
+        self.mode = AppMode::GlobalSearch;
+        self.current_file = None;
+        self.search_query.clear();
+        self.tag_filter = None;
+        self.update_filtered_hosts();
+        self.selected = 0;
+        self.state.select(Some(0));
+        self.status_message = format!(
+            "Indexed {} hosts from {} files",
+            self.hosts.len(),
+            self.files.len()
+        );
+        for warning in warnings {
+            self.status_message.push('\n');
+            self.status_message.push_str(&warning);
+        }
     }
 
     pub fn back_to_file_selection(&mut self) {
@@ -99,6 +1016,12 @@ impl App {
         self.search_query.clear();
         self.hosts.clear();
         self.filtered_hosts.clear();
+        self.host_matches.clear();
+        self.host_rows.clear();
+        self.collapsed_groups.clear();
+        self.tag_filter = None;
+        self.marked.clear();
+        self.config_blocks.clear();
         self.current_file = None;
         self.selected = 0;
         self.state.select(Some(0));
@@ -106,6 +1029,102 @@ impl App {
         self.update_filtered_files();
     }
 
+    /// Opens the connection history from the file or host list.
+    pub fn enter_history(&mut self) {
+        if !matches!(self.mode, AppMode::FileSelection | AppMode::HostSelection) {
+            return;
+        }
+        self.view_return = self.mode.clone();
+        self.mode = AppMode::History;
+        self.search_query.clear();
+        self.update_filtered_history();
+        self.selected = 0;
+        self.state.select(Some(0));
+    }
+
+    pub fn exit_history(&mut self) {
+        self.mode = self.view_return.clone();
+        self.search_query.clear();
+        if self.mode == AppMode::HostSelection {
+            self.update_filtered_hosts();
+        } else {
+            self.update_filtered_files();
+        }
+        self.selected = 0;
+        self.state.select(Some(0));
+    }
+
+    pub fn selected_visit(&self) -> Option<&history::Visit> {
+        if self.mode != AppMode::History {
+            return None;
+        }
+        let index = *self.filtered_history.get(self.selected)?;
+        self.history.visits().get(index)
+    }
+
+    pub fn selected_host(&self) -> Option<&parser::SshHost> {
+        if !self.mode.lists_hosts() {
+            return None;
+        }
+        match self.host_rows.get(self.selected)? {
+            HostRow::Host(position) => self.filtered_hosts.get(*position).map(|(_, host)| host),
+            HostRow::Group { .. } => None,
+        }
+    }
+
+    /// Collapses or expands the section whose heading is selected. Returns
+    /// false when a host is selected instead.
+    pub fn toggle_selected_group(&mut self) -> bool {
+        let Some(HostRow::Group { title, .. }) = self.host_rows.get(self.selected) else {
+            return false;
+        };
+        let title = title.clone();
+        if !self.collapsed_groups.remove(&title) {
+            self.collapsed_groups.insert(title);
+        }
+        self.update_host_rows();
+        true
+    }
+
+    /// Lists only the hosts with the next tag found in the loaded hosts,
+    /// going back to all hosts after the last one.
+    pub fn cycle_tag_filter(&mut self) {
+        let mut tags: Vec<&String> = Vec::new();
+        for tag in self.hosts.iter().flat_map(|host| &host.tags) {
+            if !tags.iter().any(|known| known.eq_ignore_ascii_case(tag)) {
+                tags.push(tag);
+            }
+        }
+        let next = match &self.tag_filter {
+            None => tags.first(),
+            Some(current) => tags
+                .iter()
+                .position(|tag| tag.eq_ignore_ascii_case(current))
+                .and_then(|position| tags.get(position + 1)),
+        };
+        self.status_message = match next {
+            Some(tag) => format!("Showing hosts tagged {}", tag),
+            None if tags.is_empty() => "No tags in these hosts".to_string(),
+            None => "Showing all hosts".to_string(),
+        };
+        self.tag_filter = next.map(|tag| tag.to_string());
+        self.update_filtered_hosts();
+        self.selected = 0;
+        self.state.select(Some(0));
+    }
+
+    /// What ssh will use for the selected host once every matching Host and
+    /// Match block has been applied.
+    pub fn selected_effective_config(&self) -> Option<resolve::EffectiveConfig> {
+        let host = self.selected_host()?;
+        let blocks = self.config_blocks.get(&host.config_file)?;
+        Some(resolve::evaluate(
+            blocks,
+            &host.alias,
+            &resolve::local_user(),
+        ))
+    }
+
     pub fn move_up(&mut self) {
         self.selected = self.selected.saturating_sub(1);
         self.state.select(Some(self.selected));
@@ -114,8 +1133,10 @@ impl App {
 
     pub fn move_down(&mut self) {
         let max_items = match self.mode {
-            AppMode::FileSelection => self.filtered_files.len(),
-            AppMode::HostSelection | AppMode::Search => self.filtered_hosts.len(),
+            AppMode::FileSelection => self.file_rows(),
+            AppMode::History => self.filtered_history.len(),
+            AppMode::Backups => self.backups.entries().len(),
+            _ => self.host_rows.len(),
         };
         
         if self.selected < max_items.saturating_sub(1) {
@@ -129,13 +1150,16 @@ impl App {
         if self.mode == AppMode::HostSelection {
             self.mode = AppMode::Search;
             self.search_query.clear();
+            self.update_filtered_hosts();
             self.selected = 0;
             self.state.select(Some(0));
         }
     }
 
     pub fn exit_search_mode(&mut self) {
-        if self.mode == AppMode::Search {
+        if self.mode == AppMode::GlobalSearch {
+            self.back_to_file_selection();
+        } else if self.mode == AppMode::Search {
             self.mode = AppMode::HostSelection;
             self.search_query.clear();
             self.update_filtered_hosts();
@@ -150,10 +1174,14 @@ impl App {
                 self.search_query.push(c);
                 self.update_filtered_files();
             }
-            AppMode::Search => {
+            AppMode::Search | AppMode::GlobalSearch => {
                 self.search_query.push(c);
                 self.update_filtered_hosts();
             }
+            AppMode::History => {
+                self.search_query.push(c);
+                self.update_filtered_history();
+            }
             _ => {}
         }
         self.selected = 0;
@@ -166,10 +1194,14 @@ impl App {
                 self.search_query.pop();
                 self.update_filtered_files();
             }
-            AppMode::Search => {
+            AppMode::Search | AppMode::GlobalSearch => {
                 self.search_query.pop();
                 self.update_filtered_hosts();
             }
+            AppMode::History => {
+                self.search_query.pop();
+                self.update_filtered_history();
+            }
             _ => {}
         }
         self.selected = 0;
@@ -177,6 +1209,7 @@ impl App {
     }
 
     fn update_filtered_files(&mut self) {
+        self.file_matches.clear();
         if self.search_query.is_empty() {
             self.filtered_files = self
                 .files
@@ -185,17 +1218,12 @@ impl App {
                 .map(|(i, item)| (i, item.clone()))
                 .collect();
         } else {
-            self.filtered_files = self
-                .files
-                .iter()
-                .enumerate()
-                .filter(|(_, file)| {
-                    file.name
-                        .to_lowercase()
-                        .contains(&self.search_query.to_lowercase())
-                })
-                .map(|(i, file)| (i, file.clone()))
-                .collect();
+            let results = fuzzy::fuzzy_filter_files(&self.files, &self.search_query);
+            self.filtered_files = Vec::with_capacity(results.len());
+            for (index, file, positions) in results {
+                self.filtered_files.push((index, file));
+                self.file_matches.insert(index, positions);
+            }
         }
 
         let content_length = self.filtered_files.len();
@@ -203,6 +1231,7 @@ impl App {
     }
 
     fn update_filtered_hosts(&mut self) {
+        self.host_matches.clear();
         if self.search_query.is_empty() {
             self.filtered_hosts = self
                 .hosts
@@ -210,26 +1239,216 @@ impl App {
                 .enumerate()
                 .map(|(i, item)| (i, item.clone()))
                 .collect();
+            // Favourites pinned to the top, then most used first, otherwise
+            // in file order.
+            self.filtered_hosts.sort_by_cached_key(|(_, host)| {
+                (
+                    Reverse(self.saved_state.is_favourite(host)),
+                    Reverse(self.history.frecency(host)),
+                )
+            });
+        } else {
+            let results =
+                fuzzy::fuzzy_search(&self.hosts, &self.search_query, &self.history);
+            self.filtered_hosts = Vec::with_capacity(results.len());
+            for (index, host, found) in results {
+                self.filtered_hosts.push((index, host));
+                self.host_matches.insert(index, found);
+            }
+        }
+        if let Some(tag) = &self.tag_filter {
+            self.filtered_hosts
+                .retain(|(_, host)| host.tags.iter().any(|known| known.eq_ignore_ascii_case(tag)));
+        }
+        self.update_host_rows();
+    }
+
+    /// Lays out `filtered_hosts` as list rows. While browsing a file, hosts
+    /// are listed under the heading of their section, sections in file order
+    /// and hosts before the first section on top without a heading.
+    fn update_host_rows(&mut self) {
+        let grouped = self.mode == AppMode::HostSelection
+            && self.filtered_hosts.iter().any(|(_, host)| host.group.is_some());
+        if !grouped {
+            self.host_rows = (0..self.filtered_hosts.len()).map(HostRow::Host).collect();
         } else {
-            self.filtered_hosts = fuzzy::fuzzy_search(&self.hosts, &self.search_query);
+            let mut titles: Vec<Option<&String>> = vec![None];
+            for host in &self.hosts {
+                if !titles.contains(&host.group.as_ref()) {
+                    titles.push(host.group.as_ref());
+                }
+            }
+            let mut rows = Vec::new();
+            for title in titles {
+                let members: Vec<usize> = self
+                    .filtered_hosts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, host))| host.group.as_ref() == title)
+                    .map(|(position, _)| position)
+                    .collect();
+                match title {
+                    None => rows.extend(members.into_iter().map(HostRow::Host)),
+                    Some(_) if members.is_empty() => {}
+                    Some(title) => {
+                        rows.push(HostRow::Group {
+                            title: title.clone(),
+                            hosts: members.len(),
+                        });
+                        if !self.collapsed_groups.contains(title) {
+                            rows.extend(members.into_iter().map(HostRow::Host));
+                        }
+                    }
+                }
+            }
+            self.host_rows = rows;
+        }
+
+        let content_length = self.host_rows.len();
+        self.vertical_scroll_state = self.vertical_scroll_state.content_length(content_length);
+    }
+
+    /// Lists history entries newest first, keeping those whose alias or
+    /// config file name matches the search query.
+    fn update_filtered_history(&mut self) {
+        self.history_matches.clear();
+        self.filtered_history.clear();
+        for (index, visit) in self.history.visits().iter().enumerate().rev() {
+            if self.search_query.is_empty() {
+                self.filtered_history.push(index);
+                continue;
+            }
+            let alias =
+                fuzzy::match_text(&visit.alias, &self.search_query, fuzzy::MatchKind::Fuzzy);
+            let file_name = visit
+                .config_file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if let Some((_, positions)) = alias {
+                self.filtered_history.push(index);
+                self.history_matches.insert(index, positions);
+            } else if fuzzy::match_text(&file_name, &self.search_query, fuzzy::MatchKind::Fuzzy)
+                .is_some()
+            {
+                self.filtered_history.push(index);
+            }
         }
 
-        let content_length = self.filtered_hosts.len();
+        let content_length = self.filtered_history.len();
         self.vertical_scroll_state = self.vertical_scroll_state.content_length(content_length);
     }
 
+    /// Whether `host` is a production host, and whether connecting to it
+    /// needs confirmation.
+    pub fn protection(&self, host: &parser::SshHost) -> Option<safety::Protection> {
+        safety::protection(&self.settings.production, host)
+    }
+
+    /// One host of the host list: favourite star, alias with its production
+    /// badge, the file it comes from in global search, and the field the
+    /// search matched.
+    fn host_line(
+        &self,
+        index: usize,
+        host: &parser::SshHost,
+        alias_width: usize,
+        match_style: Style,
+    ) -> Line<'static> {
+        let found = self.host_matches.get(&index);
+        let alias_positions = found
+            .filter(|found| found.field == fuzzy::HostField::Alias)
+            .map(|found| found.positions.as_slice());
+        let alias = format!("{:<width$}", host.alias, width = alias_width);
+        let star = if self.saved_state.is_favourite(host) {
+            "★ "
+        } else {
+            "  "
+        };
+        let production = &self.settings.production;
+        let protected = self.protection(host).is_some();
+        let alias_style = if protected {
+            Style::default().fg(production.colour)
+        } else {
+            Style::default()
+        };
+        let mut spans = vec![Span::styled(star, Style::default().fg(self.settings.theme.accent))];
+        if !self.marked.is_empty() {
+            let mark = if self.is_marked(host) { "✓ " } else { "  " };
+            let accent = Style::default().fg(self.settings.theme.accent);
+            spans.insert(0, Span::styled(mark, accent));
+        }
+        spans.extend(highlight_matches(
+            &alias,
+            alias_positions.unwrap_or_default(),
+            alias_style,
+            match_style,
+        ));
+        if protected && !production.badge.is_empty() {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                format!(" {} ", production.badge),
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(production.colour)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        if self.mode == AppMode::GlobalSearch {
+            // Second column: the file the host is declared in.
+            let file_name = host
+                .source_file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            spans.push(Span::styled(
+                format!("  {}", file_name),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        // Show what matched when it is not the alias itself.
+        if let Some(found) = found.filter(|found| found.field != fuzzy::HostField::Alias) {
+            let field_style = Style::default().fg(self.settings.theme.accent);
+            spans.push(Span::styled(format!("  {}: ", found.field.label()), field_style));
+            spans.extend(highlight_matches(
+                &found.text,
+                &found.positions,
+                field_style,
+                match_style,
+            ));
+        }
+        Line::from(spans)
+    }
+
     pub fn get_current_items_display(&self) -> (Vec<ListItem<'static>>, String) {
+        let match_style = Style::default()
+            .fg(self.settings.theme.accent)
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
         match self.mode {
             AppMode::FileSelection => {
-                let items: Vec<ListItem<'static>> = self
-                    .filtered_files
-                    .iter()
+                let favourites = self.lists_favourites().then(|| {
+                    Line::from(Span::styled(
+                        format!("★ Favourites ({})", self.saved_state.favourites.len()),
+                        Style::default().fg(self.settings.theme.accent),
+                    ))
+                });
+                let files = self.filtered_files.iter().map(|(index, file)| {
+                    let positions = self.file_matches.get(index).map(Vec::as_slice);
+                    Line::from(highlight_matches(
+                        &file.name,
+                        positions.unwrap_or_default(),
+                        Style::default(),
+                        match_style,
+                    ))
+                });
+                let items: Vec<ListItem<'static>> = favourites
+                    .into_iter()
+                    .chain(files)
                     .enumerate()
-                    .map(|(i, (_, file))| {
-                        let display_text = Line::from(file.name.clone());
+                    .map(|(i, display_text)| {
                         if i == self.selected {
                             ListItem::new(display_text)
-                                .style(Style::default().fg(Color::Yellow))
+                                .style(Style::default().fg(self.settings.theme.highlight))
                         } else {
                             ListItem::new(display_text)
                         }
@@ -244,31 +1463,54 @@ impl App {
                 
                 (items, title)
             }
-            AppMode::HostSelection | AppMode::Search => {
-                let items: Vec<ListItem<'static>> = self
+            AppMode::HostSelection | AppMode::Search | AppMode::GlobalSearch => {
+                let alias_width = self
                     .filtered_hosts
                     .iter()
+                    .map(|(_, host)| host.alias.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let items: Vec<ListItem<'static>> = self
+                    .host_rows
+                    .iter()
                     .enumerate()
-                    .map(|(i, (_, item))| {
-                        // Clone the Line content to create owned data
-                        let owned_line = Line::from(
-                            item.spans
-                                .iter()
-                                .map(|span| span.content.clone().into_owned())
-                                .collect::<Vec<String>>()
-                                .join("")
-                        );
-                        
+                    .map(|(i, row)| {
+                        let line = match row {
+                            HostRow::Group { title, hosts } => {
+                                let marker = if self.collapsed_groups.contains(title) {
+                                    "▸"
+                                } else {
+                                    "▾"
+                                };
+                                Line::from(Span::styled(
+                                    format!("{} {} ({})", marker, title, hosts),
+                                    Style::default()
+                                        .fg(self.settings.theme.accent)
+                                        .add_modifier(Modifier::BOLD),
+                                ))
+                            }
+                            HostRow::Host(position) => {
+                                let (index, host) = &self.filtered_hosts[*position];
+                                self.host_line(*index, host, alias_width, match_style)
+                            }
+                        };
+
                         if i == self.selected {
-                            ListItem::new(owned_line)
-                                .style(Style::default().fg(Color::Yellow))
+                            ListItem::new(line)
+                                .style(Style::default().fg(self.settings.theme.highlight))
                         } else {
-                            ListItem::new(owned_line)
+                            ListItem::new(line)
                         }
                     })
                     .collect();
 
-                let title = if self.mode == AppMode::Search {
+                let mut title = if self.mode == AppMode::GlobalSearch {
+                    format!(
+                        "All Hosts - {} files (Filtered: {})",
+                        self.files.len(),
+                        self.filtered_hosts.len()
+                    )
+                } else if self.mode == AppMode::Search {
                     format!("SSH Hosts (Filtered: {})", self.filtered_hosts.len())
                 } else {
                     format!("SSH Hosts - {}", 
@@ -277,68 +1519,412 @@ impl App {
                             .map(|f| f.name.as_str())
                             .unwrap_or("Unknown"))
                 };
+                if let Some(tag) = &self.tag_filter {
+                    title.push_str(&format!(" [tag: {}]", tag));
+                }
                 
                 (items, title)
             }
+            AppMode::History => {
+                let visits = self.history.visits();
+                let alias_width = self
+                    .filtered_history
+                    .iter()
+                    .map(|&index| visits[index].alias.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let items: Vec<ListItem<'static>> = self
+                    .filtered_history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &index)| {
+                        let visit = &visits[index];
+                        let dim = Style::default().fg(Color::DarkGray);
+                        let mut spans = vec![Span::styled(
+                            format!("{}  ", history::format_timestamp(visit.timestamp)),
+                            dim,
+                        )];
+                        let alias = format!("{:<width$}", visit.alias, width = alias_width);
+                        let positions = self.history_matches.get(&index).map(Vec::as_slice);
+                        spans.extend(highlight_matches(
+                            &alias,
+                            positions.unwrap_or_default(),
+                            Style::default(),
+                            match_style,
+                        ));
+                        let file_name = visit
+                            .config_file
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        spans.push(Span::styled(
+                            format!("  {}  {}  ", file_name, visit.launcher),
+                            dim,
+                        ));
+                        let outcome_style = if visit.outcome.is_success() {
+                            Style::default()
+                        } else {
+                            Style::default().fg(Color::Red)
+                        };
+                        spans.push(Span::styled(visit.outcome.to_string(), outcome_style));
+                        if visit.duration > 0 {
+                            spans.push(Span::styled(
+                                format!("  {}", history::format_duration(visit.duration)),
+                                dim,
+                            ));
+                        }
+
+                        if i == self.selected {
+                            ListItem::new(Line::from(spans))
+                                .style(Style::default().fg(self.settings.theme.highlight))
+                        } else {
+                            ListItem::new(Line::from(spans))
+                        }
+                    })
+                    .collect();
+
+                let title = format!(
+                    "Connection History, UTC ({} of {})",
+                    self.filtered_history.len(),
+                    visits.len()
+                );
+                (items, title)
+            }
+            AppMode::Backups => {
+                let entries = self.backups.entries();
+                let items: Vec<ListItem<'static>> = entries
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, backup)| {
+                        let line = Line::from(vec![
+                            Span::styled(
+                                format!("{}  ", history::format_timestamp(backup.timestamp)),
+                                Style::default().fg(Color::DarkGray),
+                            ),
+                            Span::raw(backup.original.display().to_string()),
+                        ]);
+                        if i == self.selected {
+                            ListItem::new(line)
+                                .style(Style::default().fg(self.settings.theme.highlight))
+                        } else {
+                            ListItem::new(line)
+                        }
+                    })
+                    .collect();
+                let title = format!("Config Backups, UTC ({})", entries.len());
+                (items, title)
+            }
         }
     }
 
-    pub fn tmux_session(&mut self) -> Result<()> {
-        disable_raw_mode()?;
-        stdout().execute(Clear(ClearType::All))?;
-
-        if self.filtered_hosts.is_empty() {
+    /// Opens an ssh session to the selected host, or the host of the selected
+    /// history entry. Production hosts that need confirmation open the
+    /// confirmation dialog instead.
+    pub fn connect(&mut self) -> Result<()> {
+        let target = match self.selected_visit() {
+            Some(visit) => Some(launcher::LaunchTarget {
+                alias: visit.alias.clone(),
+                config_file: visit.config_file.clone(),
+            }),
+            None => self.selected_host().map(|host| launcher::LaunchTarget {
+                alias: host.alias.clone(),
+                config_file: host.config_file.clone(),
+            }),
+        };
+        let Some(target) = target else {
             self.status_message = "No host to connect to".to_string();
-            enable_raw_mode()?;
+            return Ok(());
+        };
+        if self.target_protection(&target) == Some(safety::Protection::Confirm) {
+            self.confirmation = Some(safety::Confirmation::new(target));
             return Ok(());
         }
+        self.launch(target)
+    }
 
-        let selected_host = self.filtered_hosts[self.selected].1.clone();
-        let selected_server = selected_host
-            .spans
+    /// Protection of the host a target connects to. History entries of hosts
+    /// that are not loaded are only matched by their alias.
+    fn target_protection(&self, target: &launcher::LaunchTarget) -> Option<safety::Protection> {
+        let loaded = self
+            .hosts
             .iter()
-            .map(|span| span.content.clone())
-            .collect::<String>()
-            .trim()
-            .to_string();
-
-        let current_file = self.current_file.as_ref().unwrap();
-        let ssh_command = format!("ssh -F \"{}\" {}", current_file.path.to_string_lossy(),  &selected_server);
-
-        match Command::new("wt")
-            .arg("-w")
-            .arg("0")
-            .arg("new-tab")
-            .arg("cmd")
-            .arg("/k")
-            .arg(ssh_command)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Ok(child) => {
-                let output = child.wait_with_output()?;
-
-                let stderr_msg = String::from_utf8_lossy(&output.stderr);
-                let stdout_msg = String::from_utf8_lossy(&output.stdout);
-
-                if output.status.success() {
-                    self.status_message = format!(
-                        "Connected to: {}\nStdout: {}\nStderr: {}",
-                        &selected_server, stdout_msg, stderr_msg
-                    );
-                } else {
-                    self.status_message = format!(
-                        "Failed to connect to: {}\nStdout: {}\nStderr: {}",
-                        &selected_server, stdout_msg, stderr_msg
-                    );
-                }
+            .find(|host| host.alias == target.alias && host.config_file == target.config_file);
+        match loaded {
+            Some(host) => self.protection(host),
+            None => {
+                let host =
+                    parser::SshHost::new(target.alias.clone(), target.config_file.clone(), 0);
+                self.protection(&host)
             }
-            Err(e) => {
-                self.status_message = format!("Failed: {}", e);
+        }
+    }
+
+    pub fn type_confirmation(&mut self, c: char) {
+        if let Some(confirmation) = &mut self.confirmation {
+            confirmation.input.push(c);
+        }
+    }
+
+    pub fn erase_confirmation(&mut self) {
+        if let Some(confirmation) = &mut self.confirmation {
+            confirmation.input.pop();
+        }
+    }
+
+    pub fn cancel_confirmation(&mut self) {
+        if let Some(confirmation) = self.confirmation.take() {
+            self.status_message = format!("Cancelled connecting to {}", confirmation.target.alias);
+        }
+    }
+
+    /// Connects once the typed text is the alias of the pending host, and
+    /// keeps the dialog open otherwise.
+    pub fn confirm_connection(&mut self) -> Result<()> {
+        match self.confirmation.take() {
+            Some(confirmation) if confirmation.is_confirmed() => self.launch(confirmation.target),
+            Some(confirmation) => {
+                self.status_message = format!(
+                    "Type {} exactly to connect, or Esc to cancel",
+                    confirmation.target.alias
+                );
+                self.confirmation = Some(confirmation);
+                Ok(())
             }
+            None => Ok(()),
+        }
+    }
+
+    /// Runs ssh for `target` with the launcher chosen for it. Launchers that
+    /// run in this terminal get it back in its normal state until they exit.
+    /// Every attempt is added to the connection history.
+    fn launch(&mut self, target: launcher::LaunchTarget) -> Result<()> {
+        let launcher = launcher::for_host(&self.settings.launcher, &target.alias);
+
+        let timestamp = history::now();
+        let started = Instant::now();
+        let visit = |outcome| history::Visit {
+            timestamp,
+            alias: target.alias.clone(),
+            config_file: target.config_file.clone(),
+            launcher: launcher.name().to_string(),
+            outcome,
+            duration: started.elapsed().as_secs(),
+        };
+        // ssh takes this process over and never comes back to record the
+        // visit, so it is recorded before and corrected if ssh fails to start.
+        let replaces_process = launcher.replaces_process();
+        if replaces_process {
+            let _ = self.history.record(visit(history::Outcome::Launched));
+        }
+        let result = if launcher.takes_over_terminal() {
+            let _suspended = SuspendedTerminal::new()?;
+            launcher.launch(&target)
+        } else {
+            launcher.launch(&target)
+        };
+
+        let outcome = match &result {
+            Ok(report) => match report.exit_code {
+                Some(code) => history::Outcome::Exited(code),
+                None if report.success => history::Outcome::Launched,
+                None => history::Outcome::Failed,
+            },
+            Err(_) => history::Outcome::Failed,
+        };
+        self.status_message = match result {
+            Ok(report) => report.message,
+            Err(e) => format!(
+                "Failed to connect to {} with {}: {}",
+                target.alias,
+                launcher.name(),
+                e
+            ),
+        };
+
+        let saved = if replaces_process {
+            self.history.replace_last(visit(outcome))
+        } else {
+            self.history.record(visit(outcome))
+        };
+        if let Err(e) = saved {
+            self.status_message
+                .push_str(&format!("\nCould not save connection history: {}", e));
+        }
+        if self.mode == AppMode::History {
+            self.update_filtered_history();
+            self.selected = 0;
+            self.state.select(Some(0));
         }
-        enable_raw_mode()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_matches() {
+        let base = Style::default();
+        let matched = Style::default().fg(Color::Red);
+        let spans = highlight_matches("web-01", &[0, 1, 4], base, matched);
+        let parts: Vec<(&str, bool)> = spans
+            .iter()
+            .map(|span| (span.content.as_ref(), span.style == matched))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![("we", true), ("b-", false), ("0", true), ("1", false)]
+        );
+        assert_eq!(highlight_matches("", &[], base, matched).len(), 0);
+    }
+
+    /// An app on `files` keeping its history, state and backups in `dir`.
+    fn test_app(dir: &std::path::Path, files: Vec<PathBuf>) -> App {
+        let locations = locations::ConfigLocations {
+            files,
+            dirs: Vec::new(),
+            origin: locations::Origin::CommandLine,
+        };
+        App::with_data(
+            locations,
+            settings::Settings::default(),
+            history::History::load_from(dir.join(history::HISTORY_FILE_NAME)).unwrap(),
+            state::State::load_from(dir.join(state::STATE_FILE_NAME)).unwrap(),
+            backup::Backups::load_from(dir.join(backup::BACKUP_DIR_NAME)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_host_rows_group_sections() {
+        let dir = std::env::temp_dir().join("sshtui_app_groups_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        std::fs::write(
+            &config,
+            "Host bastion\n\
+             # ==== Paris ====\n\
+             # tags: db\n\
+             Host paris-db\n\
+             Host paris-web\n\
+             # ==== Lyon ====\n\
+             # tags: db\n\
+             Host lyon-db\n",
+        )
+        .unwrap();
+
+        let mut app = test_app(&dir, vec![config]);
+        app.load_hosts_from_selected_file();
+
+        let group = |title: &str, hosts| HostRow::Group {
+            title: title.to_string(),
+            hosts,
+        };
+        assert_eq!(
+            app.host_rows,
+            vec![
+                HostRow::Host(0),
+                group("Paris", 2),
+                HostRow::Host(1),
+                HostRow::Host(2),
+                group("Lyon", 1),
+                HostRow::Host(3),
+            ]
+        );
+
+        app.selected = 1;
+        assert!(app.toggle_selected_group());
+        assert_eq!(app.host_rows[1..3], [group("Paris", 2), group("Lyon", 1)]);
+        assert_eq!(app.selected_host(), None);
+        app.selected = 0;
+        assert!(!app.toggle_selected_group());
+        assert_eq!(app.selected_host().unwrap().alias, "bastion");
+
+        app.cycle_tag_filter();
+        assert_eq!(app.tag_filter.as_deref(), Some("db"));
+        assert_eq!(
+            app.host_rows,
+            vec![group("Paris", 1), group("Lyon", 1), HostRow::Host(1)]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rename_follows_includes() {
+        let dir = std::env::temp_dir().join("sshtui_app_rename_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        let jumps = dir.join("jumps");
+        let other = dir.join("other");
+        std::fs::write(
+            &config,
+            format!("Include {}\nHost db\n  HostName 10.0.0.1\n", jumps.display()),
+        )
+        .unwrap();
+        std::fs::write(&jumps, "Match host web\n  ProxyJump admin@db\n").unwrap();
+        std::fs::write(&other, "Host data\n").unwrap();
+
+        let mut app = test_app(&dir, vec![config.clone(), other]);
+        app.load_hosts_from_selected_file();
+        let db = app.hosts[0].clone();
+
+        // Declared in another file, the alias would be shadowed.
+        app.renaming = Some(editor::Rename {
+            host: db.clone(),
+            input: "data".to_string(),
+        });
+        app.confirm_rename();
+        assert!(app.renaming.is_some());
+        assert!(app.status_message.contains("already declared"));
+
+        app.type_rename('b');
+        app.confirm_rename();
+        assert!(app.renaming.is_none());
+        assert!(std::fs::read_to_string(&config)
+            .unwrap()
+            .contains("Host datab\n"));
+        assert_eq!(
+            std::fs::read_to_string(&jumps).unwrap(),
+            "Match host web\n  ProxyJump admin@datab\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_favourite_while_searching() {
+        use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let dir = std::env::temp_dir().join("sshtui_app_favourite_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        std::fs::write(&config, "Host web
+Host db
+").unwrap();
+
+        let mut app = test_app(&dir, vec![config]);
+        app.load_hosts_from_selected_file();
+        app.enter_search_mode();
+        app.add_char_to_search('d');
+        assert_eq!(app.selected_host().unwrap().alias, "db");
+
+        // The favourite key is a character but stars the host instead of
+        // being typed into the query.
+        let star = KeyEvent::new(KeyCode::Char('*'), KeyModifiers::NONE);
+        assert!(settings::is_text_input(&star));
+        assert_eq!(
+            app.settings.keys.action_for(&star),
+            Some(settings::Action::Favourite)
+        );
+        app.toggle_favourite();
+        assert_eq!(app.search_query, "d");
+        let db = app.selected_host().unwrap().clone();
+        assert!(app.saved_state.is_favourite(&db));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
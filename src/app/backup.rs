@@ -0,0 +1,413 @@
+//! Every write to a config file goes through here: what it replaces is copied
+//! to a timestamped backup first, and the edit is kept for undo and redo
+//! during the session.
+
+use super::history::{data_dir, now};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const BACKUP_DIR_NAME: &str = "backups";
+const INDEX_FILE_NAME: &str = "index.tsv";
+/// Backups kept for each config file, the oldest are removed first.
+const MAX_BACKUPS_PER_FILE: usize = 20;
+
+/// A copy of a config file taken before it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub timestamp: u64,
+    /// The config file the copy was taken of.
+    pub original: PathBuf,
+    /// File name of the copy in the backup folder.
+    pub name: String,
+}
+
+impl Backup {
+    /// Reads a `timestamp, original path, copy name` line of tab separated
+    /// fields.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let timestamp = fields.next()?.parse().ok()?;
+        let original = PathBuf::from(fields.next()?);
+        let name = fields.next().filter(|name| !name.is_empty())?.to_string();
+        Some(Backup {
+            timestamp,
+            original,
+            name,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}",
+            self.timestamp,
+            self.original.display(),
+            self.name
+        )
+    }
+}
+
+/// The backups in the backup folder, listed oldest first in its index file.
+#[derive(Debug, Default)]
+pub struct Backups {
+    /// The backup folder, `None` when nothing is backed up.
+    dir: Option<PathBuf>,
+    entries: Vec<Backup>,
+}
+
+impl Backups {
+    /// Loads the backups kept in the data directory.
+    pub fn load() -> io::Result<Self> {
+        match data_dir() {
+            Some(dir) => Self::load_from(dir.join(BACKUP_DIR_NAME)),
+            None => Ok(Backups::default()),
+        }
+    }
+
+    pub fn load_from(dir: PathBuf) -> io::Result<Self> {
+        let entries = match fs::read_to_string(dir.join(INDEX_FILE_NAME)) {
+            Ok(content) => content.lines().filter_map(Backup::parse).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Backups {
+            dir: Some(dir),
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[Backup] {
+        &self.entries
+    }
+
+    /// Keeps `content`, the current content of `original`, as its newest
+    /// backup.
+    pub fn create(&mut self, original: &Path, content: &str) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let timestamp = now();
+        let file_name = original
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut name = format!("{}-{}", timestamp, file_name);
+        let mut copy = 1;
+        while dir.join(&name).exists() {
+            name = format!("{}-{}-{}", timestamp, copy, file_name);
+            copy += 1;
+        }
+        fs::write(dir.join(&name), content)?;
+        self.entries.push(Backup {
+            timestamp,
+            original: original.to_path_buf(),
+            name,
+        });
+
+        let copies = self
+            .entries
+            .iter()
+            .filter(|backup| backup.original == original)
+            .count();
+        let mut excess = copies.saturating_sub(MAX_BACKUPS_PER_FILE);
+        let mut removed = Vec::new();
+        self.entries.retain(|backup| {
+            let remove = excess > 0 && backup.original == original;
+            if remove {
+                excess -= 1;
+                removed.push(backup.name.clone());
+            }
+            !remove
+        });
+        for name in removed {
+            let _ = fs::remove_file(dir.join(name));
+        }
+        self.save_index()
+    }
+
+    fn save_index(&self) -> io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let mut index = String::new();
+        for backup in &self.entries {
+            index.push_str(&backup.to_line());
+            index.push('\n');
+        }
+        write_file(&dir.join(INDEX_FILE_NAME), &index)
+    }
+
+    /// The content the config file had when `backup` was taken.
+    pub fn read(&self, backup: &Backup) -> io::Result<String> {
+        let dir = self.dir.as_deref().unwrap_or(Path::new(""));
+        fs::read_to_string(dir.join(&backup.name))
+    }
+}
+
+/// The content of `path`, `None` when it does not exist.
+pub fn read_existing(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes through a temporary file next to `path`, keeping its permissions,
+/// so an interrupted write never leaves a truncated file behind. A symlinked
+/// file is written where the link points.
+pub fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.tmp", name));
+    fs::write(&temporary, content)?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        fs::set_permissions(&temporary, metadata.permissions())?;
+    }
+    fs::rename(&temporary, &path)
+}
+
+/// One file written by an edit. `None` content is a file that does not
+/// exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl FileChange {
+    /// Backs up the `before` content and writes the `after` one.
+    fn write(&self, backups: &mut Backups) -> Result<(), String> {
+        let path = &self.path;
+        if let Some(before) = &self.before {
+            backups
+                .create(path, before)
+                .map_err(|e| format!("Cannot back up {}: {}", path.display(), e))?;
+        }
+        set_content(path, &self.after)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+    }
+}
+
+/// Writes `content` to `path`, or removes the file for `None`.
+fn set_content(path: &Path, content: &Option<String>) -> io::Result<()> {
+    match content {
+        Some(content) => write_file(path, content),
+        None => fs::remove_file(path),
+    }
+}
+
+/// Changes to one or more files made by a single action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub description: String,
+    pub changes: Vec<FileChange>,
+}
+
+impl Edit {
+    /// The edit turning the files back into what they were.
+    fn reversed(&self) -> Self {
+        Edit {
+            description: self.description.clone(),
+            changes: self
+                .changes
+                .iter()
+                .map(|change| FileChange {
+                    path: change.path.clone(),
+                    before: change.after.clone(),
+                    after: change.before.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes every change after checking no file changed since its `before`
+    /// content was read, backing up what each write replaces. When a file
+    /// cannot be written, the files already written get their `before`
+    /// content back, so the edit is made entirely or not at all.
+    pub fn apply(&self, backups: &mut Backups) -> Result<(), String> {
+        for change in &self.changes {
+            let current = read_existing(&change.path)
+                .map_err(|e| format!("Cannot read {}: {}", change.path.display(), e))?;
+            if current != change.before {
+                return Err(format!(
+                    "{} was changed by something else, leaving it as it is",
+                    change.path.display()
+                ));
+            }
+        }
+        for (count, change) in self.changes.iter().enumerate() {
+            if let Err(e) = change.write(backups) {
+                let mut message = e;
+                for written in self.changes[..count].iter().rev() {
+                    if let Err(e) = set_content(&written.path, &written.before) {
+                        message.push_str(&format!(
+                            ", and {} could not be put back: {}",
+                            written.path.display(),
+                            e
+                        ));
+                    }
+                }
+                return Err(message);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Edits made in this session that can be undone, and undone edits that can
+/// be made again.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl UndoStack {
+    /// Adds an edit that was just made. Edits undone before it can no longer
+    /// be redone.
+    pub fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Reverts the last edit. Returns its description, or `None` when there is
+    /// nothing to undo.
+    pub fn undo(&mut self, backups: &mut Backups) -> Option<Result<String, String>> {
+        let edit = self.undo.pop()?;
+        Some(match edit.reversed().apply(backups) {
+            Ok(()) => {
+                let description = edit.description.clone();
+                self.redo.push(edit);
+                Ok(description)
+            }
+            Err(e) => {
+                self.undo.push(edit);
+                Err(e)
+            }
+        })
+    }
+
+    /// Makes the last undone edit again.
+    pub fn redo(&mut self, backups: &mut Backups) -> Option<Result<String, String>> {
+        let edit = self.redo.pop()?;
+        Some(match edit.apply(backups) {
+            Ok(()) => {
+                let description = edit.description.clone();
+                self.undo.push(edit);
+                Ok(description)
+            }
+            Err(e) => {
+                self.redo.push(edit);
+                Err(e)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_undo_redo() {
+        let dir = std::env::temp_dir().join("sshtui_backup_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        fs::write(&config, "Host web\n").unwrap();
+        let mut backups = Backups::load_from(dir.join(BACKUP_DIR_NAME)).unwrap();
+        let mut stack = UndoStack::default();
+
+        let edit = Edit {
+            description: "Edit web".to_string(),
+            changes: vec![FileChange {
+                path: config.clone(),
+                before: Some("Host web\n".to_string()),
+                after: Some("Host www\n".to_string()),
+            }],
+        };
+        edit.apply(&mut backups).unwrap();
+        stack.record(edit);
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host www\n");
+
+        assert_eq!(stack.undo(&mut backups), Some(Ok("Edit web".to_string())));
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host web\n");
+        assert_eq!(stack.undo(&mut backups), None);
+        fs::write(&config, "Host changed\n").unwrap();
+        assert!(stack.redo(&mut backups).unwrap().is_err());
+        fs::write(&config, "Host web\n").unwrap();
+        assert!(stack.redo(&mut backups).unwrap().is_ok());
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host www\n");
+
+        // The edit, the undo and the redo each backed up what they replaced.
+        let reloaded = Backups::load_from(dir.join(BACKUP_DIR_NAME)).unwrap();
+        let contents: Vec<String> = reloaded
+            .entries()
+            .iter()
+            .map(|backup| reloaded.read(backup).unwrap())
+            .collect();
+        assert_eq!(contents, vec!["Host web\n", "Host www\n", "Host web\n"]);
+        assert!(reloaded
+            .entries()
+            .iter()
+            .all(|backup| backup.original == config));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_edit_is_rolled_back() {
+        let dir = std::env::temp_dir().join("sshtui_backup_rollback_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config");
+        fs::write(&config, "Host web\n").unwrap();
+        let mut backups = Backups::load_from(dir.join(BACKUP_DIR_NAME)).unwrap();
+
+        // The second file's folder does not exist, so it cannot be written.
+        let edit = Edit {
+            description: "Move web".to_string(),
+            changes: vec![
+                FileChange {
+                    path: config.clone(),
+                    before: Some("Host web\n".to_string()),
+                    after: Some(String::new()),
+                },
+                FileChange {
+                    path: dir.join("missing").join("config"),
+                    before: None,
+                    after: Some("Host web\n".to_string()),
+                },
+            ],
+        };
+        assert!(edit.apply(&mut backups).is_err());
+        assert_eq!(fs::read_to_string(&config).unwrap(), "Host web\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_backups() {
+        let dir = std::env::temp_dir().join("sshtui_backup_prune_test");
+        let _ = fs::remove_dir_all(&dir);
+        let mut backups = Backups::load_from(dir.clone()).unwrap();
+        for i in 0..MAX_BACKUPS_PER_FILE + 2 {
+            backups
+                .create(Path::new("/etc/ssh/a"), &i.to_string())
+                .unwrap();
+        }
+        backups.create(Path::new("/etc/ssh/b"), "b").unwrap();
+
+        let reloaded = Backups::load_from(dir.clone()).unwrap();
+        assert_eq!(reloaded.entries().len(), MAX_BACKUPS_PER_FILE + 1);
+        assert_eq!(reloaded.read(&reloaded.entries()[0]).unwrap(), "2");
+        let copies = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(copies, MAX_BACKUPS_PER_FILE + 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
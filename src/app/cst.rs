@@ -0,0 +1,346 @@
+//! A concrete syntax tree of an ssh config file. Every line keeps its
+//! indentation, keyword as written, separator, trailing comment and line
+//! ending, so an untouched file is written back byte for byte and an edit
+//! changes only the lines it touches.
+
+use super::parser::{split_args, tokenize_line};
+use std::{fs, io, ops::Range, path::Path};
+
+/// A keyword line. Its parts written one after the other give back the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub indent: String,
+    /// The keyword with its casing as written.
+    pub keyword: String,
+    /// Whitespace and `=` between the keyword and the value.
+    pub separator: String,
+    /// The arguments as written, quotes included.
+    pub value: String,
+    /// Whitespace after the value and the comment ending the line, if any.
+    pub trailing: String,
+}
+
+impl Directive {
+    /// A new line `<indent><keyword> <value>`.
+    pub fn new(indent: &str, keyword: &str, value: &str) -> Self {
+        Self {
+            indent: indent.to_string(),
+            keyword: keyword.to_string(),
+            separator: " ".to_string(),
+            value: value.to_string(),
+            trailing: String::new(),
+        }
+    }
+
+    pub fn is(&self, keyword: &str) -> bool {
+        self.keyword.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Replaces the arguments, keeping the indentation, keyword, separator
+    /// and trailing comment.
+    pub fn set_value(&mut self, value: &str) {
+        if self.separator.is_empty() {
+            self.separator = " ".to_string();
+        }
+        if self.trailing.starts_with('#') {
+            self.trailing.insert(0, ' ');
+        }
+        self.value = value.to_string();
+    }
+
+    /// The arguments with quotes removed.
+    pub fn args(&self) -> Vec<String> {
+        split_args(&self.value).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// An empty or whitespace-only line.
+    Blank(String),
+    Comment {
+        indent: String,
+        /// The comment from its `#` on.
+        text: String,
+    },
+    Directive(Directive),
+    /// A line ssh cannot read, such as one with unbalanced quotes, kept as
+    /// written.
+    Invalid(String),
+}
+
+impl Node {
+    pub fn parse(text: &str) -> Self {
+        let content = text.trim_start();
+        let indent = &text[..text.len() - content.len()];
+        if content.is_empty() {
+            return Node::Blank(text.to_string());
+        }
+        if content.starts_with('#') {
+            return Node::Comment {
+                indent: indent.to_string(),
+                text: content.to_string(),
+            };
+        }
+        if tokenize_line(text).is_none() {
+            return Node::Invalid(text.to_string());
+        }
+
+        let keyword_end = content
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(content.len());
+        let after_keyword = &content[keyword_end..];
+        let rest = after_keyword.trim_start();
+        let rest = rest.strip_prefix('=').unwrap_or(rest).trim_start();
+        let value = rest[..comment_start(rest)].trim_end();
+        Node::Directive(Directive {
+            indent: indent.to_string(),
+            keyword: content[..keyword_end].to_string(),
+            separator: after_keyword[..after_keyword.len() - rest.len()].to_string(),
+            value: value.to_string(),
+            trailing: rest[value.len()..].to_string(),
+        })
+    }
+
+    /// The line as written, without its line ending.
+    pub fn text(&self) -> String {
+        match self {
+            Node::Blank(text) | Node::Invalid(text) => text.clone(),
+            Node::Comment { indent, text } => format!("{}{}", indent, text),
+            Node::Directive(directive) => format!(
+                "{}{}{}{}{}",
+                directive.indent,
+                directive.keyword,
+                directive.separator,
+                directive.value,
+                directive.trailing
+            ),
+        }
+    }
+
+    pub fn directive(&self) -> Option<&Directive> {
+        match self {
+            Node::Directive(directive) => Some(directive),
+            _ => None,
+        }
+    }
+
+    /// The text of a comment line after its `#`, trimmed.
+    pub fn comment(&self) -> Option<&str> {
+        match self {
+            Node::Comment { text, .. } => Some(text[1..].trim()),
+            _ => None,
+        }
+    }
+
+    pub fn is_blank(&self) -> bool {
+        matches!(self, Node::Blank(_))
+    }
+}
+
+/// Where the comment ending a line starts in the text after its keyword: at
+/// a `#` opening an argument, as [`split_args`] reads it.
+fn comment_start(rest: &str) -> usize {
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = rest.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if !in_arg {
+            if c.is_whitespace() {
+                continue;
+            }
+            if c == '#' {
+                return index;
+            }
+            in_arg = true;
+        }
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if matches!(chars.peek(), Some((_, '"' | '\\'))) => {
+                chars.next();
+            }
+            c if c.is_whitespace() && !in_quotes => in_arg = false,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub node: Node,
+    /// `\n`, `\r\n`, or empty for a last line without one.
+    pub ending: String,
+}
+
+/// A config file as a list of lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigFile {
+    pub lines: Vec<Line>,
+}
+
+impl ConfigFile {
+    pub fn parse(content: &str) -> Self {
+        let lines = content
+            .split_inclusive('\n')
+            .map(|line| {
+                let text = line
+                    .strip_suffix("\r\n")
+                    .or_else(|| line.strip_suffix('\n'))
+                    .unwrap_or(line);
+                Line {
+                    node: Node::parse(text),
+                    ending: line[text.len()..].to_string(),
+                }
+            })
+            .collect();
+        Self { lines }
+    }
+
+    /// Reads `path`, a missing file being empty.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut content = String::new();
+        for line in &self.lines {
+            content.push_str(&line.node.text());
+            content.push_str(&line.ending);
+        }
+        content
+    }
+
+    /// The line ending new lines get: the file's first one, or `\n`.
+    fn newline(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.ending.as_str())
+            .find(|ending| !ending.is_empty())
+            .unwrap_or("\n")
+            .to_string()
+    }
+
+    pub fn node(&self, index: usize) -> Option<&Node> {
+        self.lines.get(index).map(|line| &line.node)
+    }
+
+    pub fn directive(&self, index: usize) -> Option<&Directive> {
+        self.node(index)?.directive()
+    }
+
+    pub fn directive_mut(&mut self, index: usize) -> Option<&mut Directive> {
+        match &mut self.lines.get_mut(index)?.node {
+            Node::Directive(directive) => Some(directive),
+            _ => None,
+        }
+    }
+
+    /// Inserts lines before `index`. A last line without a line ending gets
+    /// one when lines are added after it.
+    pub fn insert(&mut self, index: usize, nodes: impl IntoIterator<Item = Node>) {
+        let newline = self.newline();
+        if index == self.lines.len() {
+            if let Some(last) = self.lines.last_mut().filter(|last| last.ending.is_empty()) {
+                last.ending = newline.clone();
+            }
+        }
+        let lines = nodes.into_iter().map(|node| Line {
+            node,
+            ending: newline.clone(),
+        });
+        self.lines.splice(index..index, lines);
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.lines.drain(range);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_nodes() {
+        let file = ConfigFile::parse(
+            "# top\r\n\r\nHost web \"my host\"  # main\r\n\tuser=  admin\r\n  Bad \"quote",
+        );
+        let nodes: Vec<&Node> = file.lines.iter().map(|line| &line.node).collect();
+        assert_eq!(nodes[0].comment(), Some("top"));
+        assert!(nodes[1].is_blank());
+        assert_eq!(
+            nodes[2].directive(),
+            Some(&Directive {
+                indent: String::new(),
+                keyword: "Host".to_string(),
+                separator: " ".to_string(),
+                value: "web \"my host\"".to_string(),
+                trailing: "  # main".to_string(),
+            })
+        );
+        assert_eq!(nodes[2].directive().unwrap().args(), vec!["web", "my host"]);
+        let user = nodes[3].directive().unwrap();
+        assert_eq!(
+            (user.keyword.as_str(), user.separator.as_str()),
+            ("user", "=  ")
+        );
+        assert_eq!(nodes[4], &Node::Invalid("  Bad \"quote".to_string()));
+        assert_eq!(file.lines[4].ending, "");
+
+        let mut file = file;
+        file.insert(file.lines.len(), [Node::Blank(String::new())]);
+        assert!(file.render().ends_with("\"quote\r\n\r\n"));
+    }
+
+    /// Lines made of the characters that matter to the syntax.
+    fn config_text() -> impl Strategy<Value = String> {
+        let line = prop_oneof![
+            "[ \t]{0,3}(Host|HostName|User|Port|Match|#|=)[ \t=]{0,3}[a-z0-9 \t\"#=\\\\]{0,20}",
+            "[ \t\"#=\\\\a-z]{0,10}",
+            "\\PC{0,20}",
+        ];
+        let ending = prop_oneof!["\n", "\r\n", "\r", ""];
+        prop::collection::vec((line, ending), 0..12)
+            .prop_map(|lines| lines.into_iter().map(|(line, end)| line + &end).collect())
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_is_identity(content in config_text()) {
+            let file = ConfigFile::parse(&content);
+            prop_assert_eq!(file.render(), content);
+            for line in &file.lines {
+                // Directives read the same arguments as the parser.
+                if let Node::Directive(directive) = &line.node {
+                    let (keyword, args) = tokenize_line(&line.node.text()).unwrap();
+                    prop_assert_eq!(&keyword, &directive.keyword);
+                    prop_assert_eq!(args, directive.args());
+                }
+            }
+        }
+
+        #[test]
+        fn edits_touch_only_their_line(content in config_text(), value in "[a-z0-9.]{1,10}") {
+            let mut file = ConfigFile::parse(&content);
+            let Some(index) = file.lines.iter().position(|line| line.node.directive().is_some())
+            else {
+                return Ok(());
+            };
+            file.directive_mut(index).unwrap().set_value(&value);
+            let before = ConfigFile::parse(&content);
+            let after = ConfigFile::parse(&file.render());
+            prop_assert_eq!(before.lines.len(), after.lines.len());
+            for (i, (old, new)) in before.lines.iter().zip(&after.lines).enumerate() {
+                if i != index {
+                    prop_assert_eq!(old, new);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Line diffs of config files, shown before an edit touching several hosts
+//! is written.
+
+/// Lines shown around each change.
+const CONTEXT: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub change: Change,
+    /// Line number in the old content, or in the new one for an added line.
+    pub line: usize,
+    pub text: String,
+}
+
+/// The lines removed from `before` and added in `after`, each run of changes
+/// with `CONTEXT` unchanged lines around it. Empty when nothing changed.
+pub fn diff_lines(before: &str, after: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes, filled from the end.
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut all = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let line = |change, line: usize, text: &str| DiffLine {
+            change,
+            line: line + 1,
+            text: text.to_string(),
+        };
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            all.push(line(Change::Same, i, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            all.push(line(Change::Removed, i, old[i]));
+            i += 1;
+        } else {
+            all.push(line(Change::Added, j, new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..all.len())
+        .filter(|&index| all[index].change != Change::Same)
+        .collect();
+    all.into_iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            changed
+                .iter()
+                .any(|&change| change.abs_diff(*index) <= CONTEXT)
+        })
+        .map(|(_, line)| line)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let before = "Host a\n  User root\n  Port 22\n\nHost b\n  HostName b\n  User root\n";
+        let after = "Host a\n  User admin\n  Port 22\n\nHost b\n  HostName b\n";
+        let lines: Vec<(Change, usize, String)> = diff_lines(before, after)
+            .into_iter()
+            .map(|line| (line.change, line.line, line.text))
+            .collect();
+        let line = |change, number, text: &str| (change, number, text.to_string());
+        assert_eq!(
+            lines,
+            vec![
+                line(Change::Same, 1, "Host a"),
+                line(Change::Removed, 2, "  User root"),
+                line(Change::Added, 2, "  User admin"),
+                line(Change::Same, 3, "  Port 22"),
+                line(Change::Same, 6, "  HostName b"),
+                line(Change::Removed, 7, "  User root"),
+            ]
+        );
+        assert!(diff_lines(before, before).is_empty());
+    }
+}
@@ -0,0 +1,1030 @@
+//! Adding, editing and deleting host blocks from the host form. Edits change
+//! only the lines they have to, so comments and layout elsewhere in the file
+//! are kept.
+
+use super::cst::{ConfigFile, Directive, Node};
+use super::diff::{diff_lines, DiffLine};
+use super::parser::{is_concrete_alias, section_header, SshHost};
+use std::path::{Path, PathBuf};
+
+/// Options with their own row in the host form, after the alias.
+pub const OPTION_FIELDS: [&str; 5] = ["HostName", "User", "Port", "IdentityFile", "ProxyJump"];
+
+/// Rows of `HostForm::values` before the extra options.
+const FIXED_ROWS: usize = 1 + OPTION_FIELDS.len();
+
+/// Indentation of options added to a block without any.
+const INDENT: &str = "    ";
+
+/// Index of the `Host` line declaring `alias`, if any.
+fn declaration(file: &ConfigFile, alias: &str) -> Option<usize> {
+    (0..file.lines.len()).find(|&index| {
+        file.directive(index).is_some_and(|directive| {
+            directive.is("host") && directive.args().contains(&alias.to_string())
+        })
+    })
+}
+
+/// The lines making up a host's block in its file.
+struct BlockLines {
+    header: usize,
+    /// The `Host` line's patterns.
+    patterns: Vec<String>,
+    /// Indices of the option lines.
+    options: Vec<usize>,
+    /// Index after the last option, or after the header without options.
+    end: usize,
+}
+
+impl BlockLines {
+    /// Finds the block of `host`, checking the file still declares it where
+    /// it was read from.
+    fn find(file: &ConfigFile, host: &SshHost) -> Result<Self, String> {
+        let header = host.line.saturating_sub(1);
+        let patterns = match file.directive(header) {
+            Some(directive) if directive.is("host") && directive.args().contains(&host.alias) => {
+                directive.args()
+            }
+            _ => {
+                return Err(format!(
+                    "{} changed since it was loaded, {} is no longer on line {}",
+                    host.source_file.display(),
+                    host.alias,
+                    host.line
+                ))
+            }
+        };
+
+        let mut block = BlockLines {
+            header,
+            patterns,
+            options: Vec::new(),
+            end: header + 1,
+        };
+        for index in header + 1..file.lines.len() {
+            let Some(directive) = file.directive(index) else {
+                continue;
+            };
+            if directive.is("host") || directive.is("match") {
+                break;
+            }
+            block.end = index + 1;
+            if !directive.is("include") {
+                block.options.push(index);
+            }
+        }
+        Ok(block)
+    }
+
+    fn option<'a>(&self, file: &'a ConfigFile, index: usize) -> &'a Directive {
+        file.directive(self.options[index])
+            .expect("option lines are directives")
+    }
+
+    /// Replaces the patterns of the `Host` line.
+    fn set_patterns(&self, file: &mut ConfigFile, patterns: &[String]) {
+        if let Some(header) = file.directive_mut(self.header) {
+            header.set_value(&patterns.join(" "));
+        }
+    }
+
+    /// Renames one alias of the `Host` line.
+    fn rename(&self, file: &mut ConfigFile, old: &str, new: &str) {
+        let patterns: Vec<String> = self
+            .patterns
+            .iter()
+            .map(|pattern| {
+                if pattern == old {
+                    new.to_string()
+                } else {
+                    pattern.clone()
+                }
+            })
+            .collect();
+        self.set_patterns(file, &patterns);
+    }
+
+    /// Index of the first of the comments right above the `Host` line, which
+    /// describe the host, or of the `Host` line without any.
+    fn start(&self, file: &ConfigFile) -> usize {
+        comments_start(file, self.header)
+    }
+}
+
+/// Index of the first of the comments right above `header`, stopping at a
+/// section header, or `header` without any.
+fn comments_start(file: &ConfigFile, header: usize) -> usize {
+    let mut start = header;
+    while let Some(comment) = start
+        .checked_sub(1)
+        .and_then(|above| file.node(above)?.comment())
+    {
+        if section_header(comment).is_some() {
+            break;
+        }
+        start -= 1;
+    }
+    start
+}
+
+/// Whether `directive` opens a block of defaults for every host: a `Host`
+/// line without a single concrete alias, or `Match all`.
+fn is_defaults(directive: &Directive) -> bool {
+    let args = directive.args();
+    if directive.is("host") {
+        return !args.iter().any(|pattern| is_concrete_alias(pattern));
+    }
+    directive.is("match") && args.len() == 1 && args[0].eq_ignore_ascii_case("all")
+}
+
+/// Where a new block goes: before the blocks of defaults ending the file,
+/// along with their comments and section header, since ssh keeps the first
+/// value it obtains and those would override the new block's options.
+/// Otherwise at the end of the file.
+fn new_block_index(file: &ConfigFile) -> usize {
+    let headers: Vec<usize> = (0..file.lines.len())
+        .filter(|&index| {
+            file.directive(index)
+                .is_some_and(|directive| directive.is("host") || directive.is("match"))
+        })
+        .collect();
+    let defaults = headers
+        .iter()
+        .rev()
+        .take_while(|&&index| file.directive(index).is_some_and(is_defaults))
+        .last();
+    let Some(&header) = defaults else {
+        return file.lines.len();
+    };
+    let start = comments_start(file, header);
+    match start
+        .checked_sub(1)
+        .and_then(|above| file.node(above)?.comment())
+    {
+        Some(comment) if section_header(comment).is_some() => start - 1,
+        _ => start,
+    }
+}
+
+/// Adds a block made of `nodes` where [`new_block_index`] puts it, separated
+/// from its neighbours by blank lines.
+fn insert_block(file: &mut ConfigFile, mut nodes: Vec<Node>) {
+    let index = new_block_index(file);
+    let blank_above = index
+        .checked_sub(1)
+        .is_some_and(|above| !file.lines[above].node.is_blank());
+    if blank_above {
+        nodes.insert(0, Node::Blank(String::new()));
+    }
+    if index < file.lines.len() {
+        nodes.push(Node::Blank(String::new()));
+    }
+    file.insert(index, nodes);
+}
+
+/// Checks `keyword` can be written in a host block.
+fn check_keyword(keyword: &str) -> Result<(), String> {
+    if keyword.is_empty() || !keyword.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Option: '{}' is not an option keyword", keyword));
+    }
+    if ["host", "match", "include"]
+        .iter()
+        .any(|denied| keyword.eq_ignore_ascii_case(denied))
+    {
+        return Err(format!("Option: {} cannot be set in a host block", keyword));
+    }
+    Ok(())
+}
+
+/// Checks `alias` names a single host and can be written on a `Host` line
+/// as it is: ssh would read a `#` as a comment and a `"` as a quote, and an
+/// alias starting with `-` as an option on its command line.
+pub fn check_alias(alias: &str) -> Result<(), String> {
+    if alias.is_empty() {
+        return Err("Host: an alias is required".to_string());
+    }
+    if alias.contains(char::is_whitespace) || alias.contains(['#', '"']) {
+        return Err(format!(
+            "Host: '{}' cannot contain spaces, '#' or '\"'",
+            alias
+        ));
+    }
+    if alias.starts_with('-') {
+        return Err(format!("Host: '{}' cannot start with '-'", alias));
+    }
+    if alias.contains(['*', '?', '!']) {
+        return Err(format!("Host: '{}' is not a single host alias", alias));
+    }
+    Ok(())
+}
+
+/// The form for a new or an existing host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostForm {
+    /// File the host is written to.
+    pub file: PathBuf,
+    /// Files a new host can be written to, empty when editing.
+    pub files: Vec<PathBuf>,
+    /// The host being edited, `None` for a new one.
+    pub editing: Option<SshHost>,
+    /// The alias, the `OPTION_FIELDS` values and then `Keyword value` rows for
+    /// any other option. The last row is kept empty for the next option.
+    pub values: Vec<String>,
+    /// Focused row: 0 is the file, then one per value.
+    pub focus: usize,
+}
+
+impl HostForm {
+    pub fn new_host(files: Vec<PathBuf>, file: PathBuf) -> Self {
+        Self {
+            file,
+            files,
+            editing: None,
+            values: vec![String::new(); FIXED_ROWS + 1],
+            focus: 1,
+        }
+    }
+
+    /// A form holding the options written in `host`'s block.
+    pub fn edit_host(host: &SshHost, file: &ConfigFile) -> Result<Self, String> {
+        let block = BlockLines::find(file, host)?;
+        let mut values = vec![host.alias.clone()];
+        let mut used = vec![false; block.options.len()];
+        for field in OPTION_FIELDS {
+            let found =
+                (0..block.options.len()).find(|&i| !used[i] && block.option(file, i).is(field));
+            values.push(match found {
+                Some(i) => {
+                    used[i] = true;
+                    block.option(file, i).value.clone()
+                }
+                None => String::new(),
+            });
+        }
+        for i in (0..block.options.len()).filter(|&i| !used[i]) {
+            let option = block.option(file, i);
+            values.push(format!("{} {}", option.keyword, option.value));
+        }
+        values.push(String::new());
+
+        Ok(Self {
+            file: host.source_file.clone(),
+            files: Vec::new(),
+            editing: Some(host.clone()),
+            values,
+            focus: 1,
+        })
+    }
+
+    /// Label of a row of the form.
+    pub fn label(&self, row: usize) -> &'static str {
+        match row {
+            0 => "File",
+            1 => "Host",
+            row if row < 1 + FIXED_ROWS => OPTION_FIELDS[row - 2],
+            _ => "Option",
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        1 + self.values.len()
+    }
+
+    pub fn next_row(&mut self) {
+        self.focus = (self.focus + 1) % self.rows();
+    }
+
+    pub fn previous_row(&mut self) {
+        self.focus = (self.focus + self.rows() - 1) % self.rows();
+    }
+
+    /// Picks the next or previous file for a new host.
+    pub fn cycle_file(&mut self, forward: bool) {
+        if self.focus != 0 || self.files.is_empty() {
+            return;
+        }
+        let count = self.files.len();
+        let current = self.files.iter().position(|file| *file == self.file);
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+            (None, _) => 0,
+        };
+        self.file = self.files[next].clone();
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        let Some(value) = self
+            .focus
+            .checked_sub(1)
+            .and_then(|i| self.values.get_mut(i))
+        else {
+            return;
+        };
+        value.push(c);
+        if self.focus == self.values.len() {
+            self.values.push(String::new());
+        }
+    }
+
+    pub fn erase(&mut self) {
+        if let Some(value) = self
+            .focus
+            .checked_sub(1)
+            .and_then(|i| self.values.get_mut(i))
+        {
+            value.pop();
+        }
+    }
+
+    pub fn alias(&self) -> &str {
+        self.values[0].trim()
+    }
+
+    /// The options to write, fixed fields first, after checking every value.
+    fn options(&self) -> Result<Vec<(String, String)>, String> {
+        check_alias(self.alias())?;
+
+        let mut options = Vec::new();
+        for (field, value) in OPTION_FIELDS.iter().zip(&self.values[1..FIXED_ROWS]) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            if *field == "Port" && !value.parse::<u16>().is_ok_and(|port| port > 0) {
+                return Err(format!("Port: '{}' is not a port number", value));
+            }
+            options.push((field.to_string(), value.to_string()));
+        }
+        for row in &self.values[FIXED_ROWS..] {
+            let row = row.trim();
+            if row.is_empty() {
+                continue;
+            }
+            let Node::Directive(option) = Node::parse(row) else {
+                return Err(format!("Option: '{}' is not an option", row));
+            };
+            if option.value.is_empty() {
+                return Err(format!(
+                    "Option: '{}' is not written as 'Keyword value'",
+                    row
+                ));
+            }
+            check_keyword(&option.keyword)?;
+            options.push((option.keyword, option.value));
+        }
+        Ok(options)
+    }
+
+    /// Writes the form into `file`, the content of `self.file`: appends a new
+    /// block, or updates the edited block line by line.
+    pub fn apply(&self, file: &mut ConfigFile) -> Result<(), String> {
+        let options = self.options()?;
+        let alias = self.alias();
+        let renamed = self.editing.as_ref().is_none_or(|host| host.alias != alias);
+        if renamed && declaration(file, alias).is_some() {
+            return Err(format!(
+                "{} is already declared in {}",
+                alias,
+                self.file.display()
+            ));
+        }
+
+        let Some(host) = &self.editing else {
+            let mut nodes = vec![Node::Directive(Directive::new("", "Host", alias))];
+            for (keyword, value) in options {
+                nodes.push(Node::Directive(Directive::new(INDENT, &keyword, &value)));
+            }
+            insert_block(file, nodes);
+            return Ok(());
+        };
+
+        let block = BlockLines::find(file, host)?;
+        if renamed {
+            block.rename(file, &host.alias, alias);
+        }
+
+        // Pair the existing lines with the wanted options of the same keyword
+        // in order: changed values are rewritten in place, lines left over
+        // removed and options left over added after the block's last line.
+        let mut wanted: Vec<Option<(String, String)>> = options.into_iter().map(Some).collect();
+        let mut removed = Vec::new();
+        for &index in &block.options {
+            let Some(option) = file.directive_mut(index) else {
+                continue;
+            };
+            let paired = wanted.iter_mut().find(|wanted| {
+                wanted
+                    .as_ref()
+                    .is_some_and(|(keyword, _)| option.is(keyword))
+            });
+            match paired.and_then(Option::take) {
+                Some((_, value)) if value != option.value => option.set_value(&value),
+                Some(_) => {}
+                None => removed.push(index),
+            }
+        }
+        let indent = match block.options.first() {
+            Some(_) => block.option(file, 0).indent.clone(),
+            None => INDENT.to_string(),
+        };
+        let added = wanted
+            .into_iter()
+            .flatten()
+            .map(|(keyword, value)| Node::Directive(Directive::new(&indent, &keyword, &value)));
+        file.insert(block.end, added);
+        for index in removed.into_iter().rev() {
+            file.remove(index..index + 1);
+        }
+        Ok(())
+    }
+}
+
+/// Removes `host` from `file`, the content of its source file. A host sharing
+/// its `Host` line with others is only taken off that line; otherwise the
+/// block goes with the comments right above it.
+pub fn delete_host(file: &mut ConfigFile, host: &SshHost) -> Result<(), String> {
+    let block = BlockLines::find(file, host)?;
+    if block.patterns.len() > 1 {
+        let patterns: Vec<String> = block
+            .patterns
+            .iter()
+            .filter(|pattern| **pattern != host.alias)
+            .cloned()
+            .collect();
+        block.set_patterns(file, &patterns);
+        return Ok(());
+    }
+
+    let start = block.start(file);
+    file.remove(start..block.end);
+
+    // Do not leave two blank lines where the block was, or one at the end.
+    let blank = |file: &ConfigFile, index: usize| file.node(index).is_none_or(Node::is_blank);
+    if start < file.lines.len() && blank(file, start) && (start == 0 || blank(file, start - 1)) {
+        file.remove(start..start + 1);
+    }
+    while file.lines.last().is_some_and(|line| line.node.is_blank()) {
+        file.lines.pop();
+    }
+    Ok(())
+}
+
+/// The lines of `host`'s block with the comments right above it, declaring
+/// only `host` when its `Host` line declares others too.
+pub fn block_nodes(file: &ConfigFile, host: &SshHost) -> Result<Vec<Node>, String> {
+    let block = BlockLines::find(file, host)?;
+    let start = block.start(file);
+    let mut nodes: Vec<Node> = file.lines[start..block.end]
+        .iter()
+        .map(|line| line.node.clone())
+        .collect();
+    if let Node::Directive(header) = &mut nodes[block.header - start] {
+        header.set_value(&host.alias);
+    }
+    Ok(nodes)
+}
+
+/// Adds a copied block declaring `alias` to `file`, the content of `path`,
+/// placed like a new host.
+pub fn append_block(
+    file: &mut ConfigFile,
+    path: &Path,
+    alias: &str,
+    nodes: Vec<Node>,
+) -> Result<(), String> {
+    if declaration(file, alias).is_some() {
+        return Err(format!(
+            "{} is already declared in {}",
+            alias,
+            path.display()
+        ));
+    }
+    insert_block(file, nodes);
+    Ok(())
+}
+
+/// Renames `host` on its `Host` line in `file`, the content of its source
+/// file.
+pub fn rename_host(file: &mut ConfigFile, host: &SshHost, alias: &str) -> Result<(), String> {
+    check_alias(alias)?;
+    if declaration(file, alias).is_some() {
+        return Err(format!(
+            "{} is already declared in {}",
+            alias,
+            host.source_file.display()
+        ));
+    }
+    BlockLines::find(file, host)?.rename(file, &host.alias, alias);
+    Ok(())
+}
+
+/// `destination` with its host replaced by `new` when it is `old`, for a
+/// `[ssh://][user@]host[:port]` destination.
+fn renamed_destination(destination: &str, old: &str, new: &str) -> Option<String> {
+    let (scheme, rest) = match destination.strip_prefix("ssh://") {
+        Some(rest) => ("ssh://", rest),
+        None => ("", destination),
+    };
+    let (user, rest) = match rest.rsplit_once('@') {
+        Some((user, rest)) => (&destination[..scheme.len() + user.len() + 1], rest),
+        None => (scheme, rest),
+    };
+    let (host, port) = match rest.split_once(':') {
+        Some((host, _)) => (host, &rest[host.len()..]),
+        None => (rest, ""),
+    };
+    (host == old).then(|| format!("{}{}{}", user, new, port))
+}
+
+/// Points the `ProxyJump` hops and `ProxyCommand` arguments naming `old` at
+/// `new`. Returns how many lines changed.
+pub fn rename_references(file: &mut ConfigFile, old: &str, new: &str) -> usize {
+    let mut changed = 0;
+    for index in 0..file.lines.len() {
+        let Some(directive) = file.directive_mut(index) else {
+            continue;
+        };
+        let separator = if directive.is("proxyjump") {
+            ','
+        } else if directive.is("proxycommand") {
+            ' '
+        } else {
+            continue;
+        };
+        let mut renamed = false;
+        let parts: Vec<String> = directive
+            .value
+            .split(separator)
+            .map(|part| match renamed_destination(part.trim(), old, new) {
+                Some(destination) => {
+                    renamed = true;
+                    part.replace(part.trim(), &destination)
+                }
+                None => part.to_string(),
+            })
+            .collect();
+        if renamed {
+            directive.set_value(&parts.join(&separator.to_string()));
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Sets `keyword` to `value` in `host`'s block, or removes it for `None`. An
+/// option written several times is left written once.
+pub fn set_option(
+    file: &mut ConfigFile,
+    host: &SshHost,
+    keyword: &str,
+    value: Option<&str>,
+) -> Result<(), String> {
+    let block = BlockLines::find(file, host)?;
+    let mut found = (0..block.options.len()).filter(|&i| block.option(file, i).is(keyword));
+    let kept = value.and_then(|value| {
+        let first = found.next()?;
+        Some((block.options[first], value))
+    });
+    let removed: Vec<usize> = found.map(|i| block.options[i]).collect();
+
+    match (kept, value) {
+        (Some((index, value)), _) => {
+            let option = file
+                .directive_mut(index)
+                .expect("option lines are directives");
+            if option.value != value {
+                option.set_value(value);
+            }
+        }
+        (None, Some(value)) => {
+            let indent = match block.options.first() {
+                Some(_) => block.option(file, 0).indent.clone(),
+                None => INDENT.to_string(),
+            };
+            let option = Directive::new(&indent, keyword, value);
+            file.insert(block.end, [Node::Directive(option)]);
+        }
+        (None, None) => {}
+    }
+    for index in removed.into_iter().rev() {
+        file.remove(index..index + 1);
+    }
+    Ok(())
+}
+
+/// The changes a bulk edit makes to one config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    /// The content the preview was made from.
+    pub before: Option<String>,
+    pub after: ConfigFile,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Setting, changing or removing one option on several hosts at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkEdit {
+    pub hosts: Vec<SshHost>,
+    pub keyword: String,
+    /// The new value, empty to remove the option.
+    pub value: String,
+    /// Whether the value is being typed rather than the keyword.
+    pub on_value: bool,
+    /// The changes to confirm, once the option is filled in.
+    pub preview: Option<Vec<FilePreview>>,
+    /// First preview line shown.
+    pub scroll: usize,
+}
+
+impl BulkEdit {
+    pub fn new(hosts: Vec<SshHost>) -> Self {
+        Self {
+            hosts,
+            keyword: String::new(),
+            value: String::new(),
+            on_value: false,
+            preview: None,
+            scroll: 0,
+        }
+    }
+
+    pub fn switch_field(&mut self) {
+        self.on_value = !self.on_value;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if self.on_value {
+            self.value.push(c);
+        } else if !c.is_whitespace() {
+            self.keyword.push(c);
+        }
+    }
+
+    pub fn erase(&mut self) {
+        if self.on_value {
+            self.value.pop();
+        } else {
+            self.keyword.pop();
+        }
+    }
+
+    /// The checked keyword, and the value or `None` to remove it.
+    fn option(&self) -> Result<(&str, Option<&str>), String> {
+        let keyword = self.keyword.trim();
+        check_keyword(keyword)?;
+        let value = self.value.trim();
+        if value.is_empty() {
+            return Ok((keyword, None));
+        }
+        match Node::parse(&format!("{} {}", keyword, value)) {
+            Node::Directive(_) => Ok((keyword, Some(value))),
+            _ => Err(format!("{}: '{}' is not a valid value", keyword, value)),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        let (keyword, value) = (self.keyword.trim(), self.value.trim());
+        let count = self.hosts.len();
+        let hosts = if count == 1 { "host" } else { "hosts" };
+        if value.is_empty() {
+            format!("Remove {} from {} {}", keyword, count, hosts)
+        } else {
+            format!("Set {} {} on {} {}", keyword, value, count, hosts)
+        }
+    }
+
+    /// Edits `file`, the content of `path`, for every host declared in it.
+    pub fn apply(&self, file: &mut ConfigFile, path: &Path) -> Result<(), String> {
+        let (keyword, value) = self.option()?;
+        let mut hosts: Vec<&SshHost> = self
+            .hosts
+            .iter()
+            .filter(|host| host.source_file == path)
+            .collect();
+        // Later blocks first, so lines added or removed do not move the
+        // blocks still to edit.
+        hosts.sort_by_key(|host| std::cmp::Reverse(host.line));
+        for host in hosts {
+            set_option(file, host, keyword, value)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the hosts' files and works out what the edit changes in each,
+    /// leaving out files it does not change.
+    pub fn make_preview(&mut self) -> Result<(), String> {
+        let mut paths: Vec<&PathBuf> = Vec::new();
+        for host in &self.hosts {
+            if !paths.contains(&&host.source_file) {
+                paths.push(&host.source_file);
+            }
+        }
+
+        let mut previews = Vec::new();
+        for path in paths {
+            let read = |e: std::io::Error| format!("Cannot read {}: {}", path.display(), e);
+            let before = super::backup::read_existing(path).map_err(read)?;
+            let mut after = ConfigFile::parse(before.as_deref().unwrap_or_default());
+            self.apply(&mut after, path)?;
+            let lines = diff_lines(before.as_deref().unwrap_or_default(), &after.render());
+            if !lines.is_empty() {
+                previews.push(FilePreview {
+                    path: path.clone(),
+                    before,
+                    after,
+                    lines,
+                });
+            }
+        }
+        if previews.is_empty() {
+            return Err(format!("{} would not change anything", self.description()));
+        }
+        self.preview = Some(previews);
+        self.scroll = 0;
+        Ok(())
+    }
+
+    /// Moves the preview one line up or down; each file takes a line for its
+    /// name and one per diff line.
+    pub fn scroll_preview(&mut self, down: bool) {
+        let rows: usize = self
+            .preview
+            .iter()
+            .flatten()
+            .map(|file| 1 + file.lines.len())
+            .sum();
+        if !down {
+            self.scroll = self.scroll.saturating_sub(1);
+        } else if self.scroll + 1 < rows {
+            self.scroll += 1;
+        }
+    }
+}
+
+/// Moving or copying a host to another config file, while its destination is
+/// picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub host: SshHost,
+    /// Whether the host stays in its file.
+    pub copy: bool,
+    /// Files the host can go to.
+    pub files: Vec<PathBuf>,
+    pub selected: usize,
+}
+
+impl Transfer {
+    pub fn move_selection(&mut self, forward: bool) {
+        let count = self.files.len().max(1);
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+}
+
+/// A host's new alias while it is typed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub host: SshHost,
+    pub input: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::resolve;
+
+    const CONFIG: &str = "\
+# ==== Paris ====
+
+# Main web server
+Host web
+  HostName 10.0.0.5
+  user   admin
+  IdentityFile ~/.ssh/a
+  IdentityFile ~/.ssh/b
+  # tags: prod
+  ForwardAgent yes
+
+Host db db-replica
+    HostName 10.0.0.6
+";
+
+    fn host(alias: &str, line: usize) -> SshHost {
+        SshHost::new(alias.to_string(), "config".into(), line)
+    }
+
+    #[test]
+    fn test_edit_host() {
+        let mut config = ConfigFile::parse(CONFIG);
+        let mut form = HostForm::edit_host(&host("web", 4), &config).unwrap();
+        assert_eq!(
+            form.values,
+            vec![
+                "web",
+                "10.0.0.5",
+                "admin",
+                "",
+                "~/.ssh/a",
+                "",
+                "IdentityFile ~/.ssh/b",
+                "ForwardAgent yes",
+                ""
+            ]
+        );
+
+        // Unchanged, the file stays as it was.
+        form.apply(&mut config).unwrap();
+        assert_eq!(config.render(), CONFIG);
+
+        form.values[0] = "www".to_string();
+        form.values[2] = "deploy".to_string();
+        form.values[3] = "2222".to_string();
+        form.values[7] = String::new();
+        form.apply(&mut config).unwrap();
+        let expected = CONFIG
+            .replace("Host web", "Host www")
+            .replace("user   admin", "user   deploy")
+            .replace("  ForwardAgent yes\n", "  Port 2222\n");
+        assert_eq!(config.render(), expected);
+
+        form.values[3] = "ssh".to_string();
+        assert!(form.apply(&mut config).unwrap_err().starts_with("Port"));
+        let mut duplicate = HostForm::edit_host(&host("www", 4), &config).unwrap();
+        duplicate.values[0] = "db-replica".to_string();
+        assert!(duplicate
+            .apply(&mut config)
+            .unwrap_err()
+            .contains("already declared"));
+        assert!(HostForm::edit_host(&host("www", 5), &config).is_err());
+    }
+
+    #[test]
+    fn test_add_and_delete_host() {
+        let mut config = ConfigFile::parse(CONFIG);
+        let mut form = HostForm::new_host(vec!["config".into()], "config".into());
+        for c in "cache".chars() {
+            form.type_char(c);
+        }
+        form.values[1] = "10.0.0.7".to_string();
+        form.focus = form.values.len();
+        for c in "Compression yes".chars() {
+            form.type_char(c);
+        }
+        assert_eq!(form.values.last().unwrap(), "");
+        form.apply(&mut config).unwrap();
+        assert_eq!(
+            config.render(),
+            format!(
+                "{}\nHost cache\n    HostName 10.0.0.7\n    Compression yes\n",
+                CONFIG
+            )
+        );
+
+        for alias in ["foo#bar", "say\"hi", "two words"] {
+            form.values[0] = alias.to_string();
+            assert!(form
+                .apply(&mut config)
+                .unwrap_err()
+                .contains("cannot contain"));
+        }
+
+        delete_host(&mut config, &host("cache", 15)).unwrap();
+        assert_eq!(config.render(), CONFIG);
+        delete_host(&mut config, &host("db-replica", 12)).unwrap();
+        delete_host(&mut config, &host("web", 4)).unwrap();
+        assert_eq!(
+            config.render(),
+            "# ==== Paris ====\n\nHost db\n    HostName 10.0.0.6\n"
+        );
+    }
+
+    #[test]
+    fn test_new_host_before_defaults() {
+        let dir = std::env::temp_dir().join("sshtui_editor_defaults_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        let defaults =
+            "Host web\n  User admin\n\n# ==== Defaults ====\nHost *\n  User root\n  Port 2200\n";
+        let mut config = ConfigFile::parse(defaults);
+
+        let mut form = HostForm::new_host(vec![path.clone()], path.clone());
+        form.values[0] = "cache".to_string();
+        form.values[2] = "deploy".to_string();
+        form.values[3] = "2222".to_string();
+        form.apply(&mut config).unwrap();
+        assert_eq!(
+            config.render(),
+            defaults.replace(
+                "\n# ====",
+                "\nHost cache\n    User deploy\n    Port 2222\n\n# ===="
+            )
+        );
+
+        std::fs::write(&path, config.render()).unwrap();
+        let (blocks, _) = resolve::read_blocks(&path, &dir);
+        let effective = resolve::evaluate(&blocks, "cache", "me");
+        assert_eq!(effective.get("User"), Some("deploy"));
+        assert_eq!(effective.get("Port"), Some("2222"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_host_before_defaults() {
+        let mut source = ConfigFile::parse(CONFIG);
+        let target = "Host bastion\n  User ops\n\nHost *\n  User root\n";
+        let mut destination = ConfigFile::parse(target);
+        let nodes = block_nodes(&source, &host("web", 4)).unwrap();
+        append_block(&mut destination, Path::new("other"), "web", nodes).unwrap();
+        delete_host(&mut source, &host("web", 4)).unwrap();
+
+        let moved = &CONFIG[CONFIG.find("# Main").unwrap()..CONFIG.find("\nHost db").unwrap()];
+        assert_eq!(
+            destination.render(),
+            target.replace("Host *", &format!("{}\nHost *", moved))
+        );
+        assert!(!source.render().contains("Host web"));
+    }
+
+    #[test]
+    fn test_copy_and_rename_host() {
+        let source = ConfigFile::parse(CONFIG);
+        let mut destination = ConfigFile::parse(
+            "Host bastion
+  ProxyJump db",
+        );
+        let nodes = block_nodes(&source, &host("db-replica", 12)).unwrap();
+        append_block(&mut destination, Path::new("other"), "db-replica", nodes).unwrap();
+        assert_eq!(
+            destination.render(),
+            "Host bastion\n  ProxyJump db\n\nHost db-replica\n    HostName 10.0.0.6\n"
+        );
+        let nodes = block_nodes(&source, &host("web", 4)).unwrap();
+        assert_eq!(nodes[0].comment(), Some("Main web server"));
+        let duplicate = block_nodes(&source, &host("db", 12)).unwrap();
+        assert!(append_block(
+            &mut destination,
+            Path::new("other"),
+            "db-replica",
+            duplicate
+        )
+        .is_err());
+
+        let mut config = ConfigFile::parse(
+            "Host a\n  ProxyJump admin@db:2222,db-replica # via db\n\
+             Host b\n  ProxyCommand ssh -W %h:%p db\nHost c\n  ProxyJump ssh://db\n",
+        );
+        assert_eq!(rename_references(&mut config, "db", "data"), 3);
+        assert_eq!(
+            config.render(),
+            "Host a\n  ProxyJump admin@data:2222,db-replica # via db\n\
+             Host b\n  ProxyCommand ssh -W %h:%p data\nHost c\n  ProxyJump ssh://data\n"
+        );
+
+        let mut config = ConfigFile::parse(CONFIG);
+        assert!(rename_host(&mut config, &host("db", 12), "db-replica").is_err());
+        assert!(rename_host(&mut config, &host("db", 12), "d b").is_err());
+        let option = rename_host(&mut config, &host("db", 12), "-oProxyCommand=x");
+        assert!(option.unwrap_err().contains("cannot start with '-'"));
+        rename_host(&mut config, &host("db", 12), "data").unwrap();
+        assert_eq!(
+            config.render(),
+            CONFIG.replace("Host db db-replica", "Host data db-replica")
+        );
+    }
+
+    #[test]
+    fn test_bulk_edit() {
+        let mut bulk = BulkEdit::new(vec![host("web", 4), host("db", 12)]);
+        for c in "User".chars() {
+            bulk.type_char(c);
+        }
+        bulk.switch_field();
+        for c in "deploy".chars() {
+            bulk.type_char(c);
+        }
+        let mut config = ConfigFile::parse(CONFIG);
+        bulk.apply(&mut config, Path::new("config")).unwrap();
+        let expected = CONFIG
+            .replace("user   admin", "user   deploy")
+            .replace("10.0.0.6\n", "10.0.0.6\n    User deploy\n");
+        assert_eq!(config.render(), expected);
+        assert_eq!(bulk.description(), "Set User deploy on 2 hosts");
+
+        let mut remove = BulkEdit::new(vec![host("web", 4)]);
+        remove.keyword = "identityfile".to_string();
+        let mut config = ConfigFile::parse(CONFIG);
+        remove.apply(&mut config, Path::new("config")).unwrap();
+        let expected = CONFIG.replace("  IdentityFile ~/.ssh/a\n  IdentityFile ~/.ssh/b\n", "");
+        assert_eq!(config.render(), expected);
+
+        remove.keyword = "Host".to_string();
+        assert!(remove.apply(&mut config, Path::new("config")).is_err());
+    }
+}
@@ -1,80 +1,436 @@
-use ratatui::text::Line;
+use super::history::History;
+use super::parser::{FileEntry, SshHost};
+use super::query::Query;
+use std::cmp::Reverse;
 
-pub fn fuzzy_search(items: &[Line<'static>], query: &str) -> Vec<(usize, Line<'static>)> {
-    let query_lower = query.to_lowercase();
-    let mut results: Vec<(usize, Line<'static>, usize)> = Vec::new();
-
-    for (index, item) in items.iter().enumerate() {
-        let item_text = item
-            .spans
-            .iter()
-            .map(|span| span.content.clone())
-            .collect::<String>()
-            .to_lowercase();
+/// The part of a host a search query matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostField {
+    Alias,
+    HostName,
+    User,
+    ProxyJump,
+    Tag,
+    Comment,
+    Port,
+    File,
+}
 
-        if let Some(score) = fuzzy_match(&item_text, &query_lower) {
-            results.push((index, item.clone(), score));
+impl HostField {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HostField::Alias => "alias",
+            HostField::HostName => "hostname",
+            HostField::User => "user",
+            HostField::ProxyJump => "proxyjump",
+            HostField::Tag => "tag",
+            HostField::Comment => "comment",
+            HostField::Port => "port",
+            HostField::File => "file",
         }
     }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMatch {
+    pub field: HostField,
+    /// The field's text that matched.
+    pub text: String,
+    /// Character indices in `text` that matched the query.
+    pub positions: Vec<usize>,
+    pub score: i32,
+}
+
+/// Values of `host` that are searched, alias first.
+fn searchable_fields(host: &SshHost) -> Vec<(HostField, &str)> {
+    let mut fields = vec![(HostField::Alias, host.alias.as_str())];
+    fields.extend(
+        host.hostname
+            .as_deref()
+            .map(|value| (HostField::HostName, value)),
+    );
+    fields.extend(host.user.as_deref().map(|value| (HostField::User, value)));
+    fields.extend(
+        host.proxy_jump
+            .as_deref()
+            .map(|value| (HostField::ProxyJump, value)),
+    );
+    fields.extend(host.tags.iter().map(|tag| (HostField::Tag, tag.as_str())));
+    fields.extend(
+        host.comments
+            .iter()
+            .map(|comment| (HostField::Comment, comment.as_str())),
+    );
+    fields
+}
+
+/// Scores every searchable field of a host and keeps the best one. Matches on
+/// the alias count in full and other fields count for three quarters, so a
+/// host named like the query ranks above one that merely mentions it.
+pub fn match_host(host: &SshHost, pattern: &str, kind: MatchKind) -> Option<FieldMatch> {
+    searchable_fields(host)
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let (score, positions) = match_text(text, pattern, kind)?;
+            let score = if field == HostField::Alias {
+                score
+            } else {
+                score * 3 / 4
+            };
+            Some(FieldMatch {
+                field,
+                text: text.to_string(),
+                positions,
+                score,
+            })
+        })
+        .max_by_key(|found| found.score)
+}
 
-    // Sort by score (higher is better)
-    results.sort_by(|a, b| b.2.cmp(&a.2));
+/// Searches hosts with a query in the syntax of [`Query::parse`]. Hosts
+/// connected to often and recently rank higher.
+pub fn fuzzy_search(
+    items: &[SshHost],
+    query: &str,
+    history: &History,
+) -> Vec<(usize, SshHost, FieldMatch)> {
+    let query = Query::parse(query);
+    let mut results: Vec<(usize, SshHost, FieldMatch)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            query
+                .matches(item)
+                .map(|found| (index, item.clone(), found))
+        })
+        .collect();
+
+    // Best score first; among equal scores the shorter text is the tighter
+    // match.
+    results.sort_by_cached_key(|(_, host, found)| {
+        (
+            Reverse(found.score + history.boost(host)),
+            found.text.chars().count(),
+        )
+    });
+    results
+}
 
-    // Return only index and item
+/// Filters config files by name, best match first, with the matched
+/// character positions of each name.
+pub fn fuzzy_filter_files(files: &[FileEntry], query: &str) -> Vec<(usize, FileEntry, Vec<usize>)> {
+    let mut results: Vec<(usize, &FileEntry, i32, Vec<usize>)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(index, file)| {
+            fuzzy_match(&file.name, query).map(|(score, positions)| (index, file, score, positions))
+        })
+        .collect();
+    results.sort_by_key(|result| (Reverse(result.2), result.1.name.chars().count()));
     results
         .into_iter()
-        .map(|(index, item, _)| (index, item))
+        .map(|(index, file, _, positions)| (index, file.clone(), positions))
         .collect()
 }
 
-fn fuzzy_match(text: &str, pattern: &str) -> Option<usize> {
-    if pattern.is_empty() {
-        return Some(0);
+// Scoring constants from fzf's algorithm. A matched character is worth
+// `SCORE_MATCH`, gaps between matches cost `SCORE_GAP_START` for the first
+// skipped character and `SCORE_GAP_EXTENSION` for each further one, and
+// characters that start a word earn a bonus.
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+const BONUS_BOUNDARY_WHITE: i32 = BONUS_BOUNDARY + 2;
+const BONUS_BOUNDARY_DELIMITER: i32 = BONUS_BOUNDARY + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    White,
+    NonWord,
+    /// Separators in host names and paths.
+    Delimiter,
+    Lower,
+    Upper,
+    Letter,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_whitespace() {
+            CharClass::White
+        } else if matches!(c, '/' | '.' | ':' | ',' | ';' | '|' | '@') {
+            CharClass::Delimiter
+        } else if c.is_lowercase() {
+            CharClass::Lower
+        } else if c.is_uppercase() {
+            CharClass::Upper
+        } else if c.is_numeric() {
+            CharClass::Number
+        } else if c.is_alphabetic() {
+            CharClass::Letter
+        } else {
+            CharClass::NonWord
+        }
+    }
+
+    fn is_word(self) -> bool {
+        !matches!(
+            self,
+            CharClass::White | CharClass::NonWord | CharClass::Delimiter
+        )
+    }
+}
+
+/// Bonus for matching a character of class `class` that follows one of
+/// class `prev`: word starts after whitespace, delimiters and punctuation,
+/// camelCase humps and the first digit of a number.
+fn bonus_for(prev: CharClass, class: CharClass) -> i32 {
+    if class.is_word() {
+        match prev {
+            CharClass::White => return BONUS_BOUNDARY_WHITE,
+            CharClass::Delimiter => return BONUS_BOUNDARY_DELIMITER,
+            CharClass::NonWord => return BONUS_BOUNDARY,
+            _ => {}
+        }
+    }
+    if (prev == CharClass::Lower && class == CharClass::Upper)
+        || (prev != CharClass::Number && class == CharClass::Number)
+    {
+        return BONUS_CAMEL_123;
+    }
+    match class {
+        CharClass::NonWord | CharClass::Delimiter => BONUS_NON_WORD,
+        CharClass::White => BONUS_BOUNDARY_WHITE,
+        _ => 0,
+    }
+}
+
+/// The bonus each character of a text earns when matched.
+fn bonuses(text: &[char]) -> Vec<i32> {
+    let mut prev_class = CharClass::White;
+    text.iter()
+        .map(|&c| {
+            let class = CharClass::of(c);
+            let bonus = bonus_for(prev_class, class);
+            prev_class = class;
+            bonus
+        })
+        .collect()
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// How a search term has to appear in a text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchKind {
+    /// Characters in order, possibly with gaps.
+    Fuzzy,
+    /// A contiguous substring.
+    Exact,
+    /// A contiguous substring at the start of the text.
+    Prefix,
+    /// A contiguous substring at the end of the text.
+    Suffix,
+    /// The whole text.
+    Equal,
+}
+
+/// Matches `pattern` against `text` the way `kind` asks, with the same
+/// smart-case rule and scoring for every kind.
+pub fn match_text(text: &str, pattern: &str, kind: MatchKind) -> Option<(i32, Vec<usize>)> {
+    if kind == MatchKind::Fuzzy {
+        return fuzzy_match(text, pattern);
     }
 
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { fold_case(c) };
     let text_chars: Vec<char> = text.chars().collect();
-    let pattern_chars: Vec<char> = pattern.chars().collect();
+    let pattern_chars: Vec<char> = pattern.chars().map(fold).collect();
+    let folded: Vec<char> = text_chars.iter().copied().map(fold).collect();
+    let (n, m) = (text_chars.len(), pattern_chars.len());
+    if m > n {
+        return None;
+    }
 
-    let mut score = 0;
-    let mut pattern_idx = 0;
-    let mut last_match_idx = 0;
+    let starts = match kind {
+        MatchKind::Prefix => 0..1,
+        MatchKind::Suffix => n - m..n - m + 1,
+        MatchKind::Equal if m == n => 0..1,
+        MatchKind::Equal => return None,
+        _ => 0..n - m + 1,
+    };
+    let bonus = bonuses(&text_chars);
+    starts
+        .filter(|&start| folded[start..start + m] == pattern_chars[..])
+        .map(|start| (run_score(&bonus[start..start + m]), start))
+        .max_by_key(|&(score, start)| (score, std::cmp::Reverse(start)))
+        .map(|(score, start)| (score, (start..start + m).collect()))
+}
 
-    for (text_idx, &text_char) in text_chars.iter().enumerate() {
-        if pattern_idx < pattern_chars.len() && text_char == pattern_chars[pattern_idx] {
-            // Consecutive matches get bonus points
-            if text_idx == last_match_idx + 1 {
-                score += 10;
+/// Score of a single run of consecutive matched characters with the given
+/// bonuses, computed like the fuzzy alignment would.
+fn run_score(bonus: &[i32]) -> i32 {
+    let mut chunk_bonus = 0;
+    bonus
+        .iter()
+        .enumerate()
+        .map(|(k, &b)| {
+            if k == 0 {
+                chunk_bonus = b;
+                return SCORE_MATCH + b * BONUS_FIRST_CHAR_MULTIPLIER;
+            }
+            if b >= BONUS_BOUNDARY && b > chunk_bonus {
+                chunk_bonus = b;
+                SCORE_MATCH + b
             } else {
-                score += 5;
+                SCORE_MATCH + b.max(BONUS_CONSECUTIVE).max(chunk_bonus)
             }
+        })
+        .sum()
+}
+
+/// Finds the best alignment of `pattern` as a subsequence of `text`, like
+/// fzf's v2 algorithm: a Smith-Waterman style dynamic programme over every
+/// pattern/text position pair rewarding word boundaries and consecutive runs
+/// and penalising gaps. Matching is smart-case: case-insensitive unless the
+/// pattern has an uppercase character. Returns the score and the character
+/// index of each matched pattern character.
+fn fuzzy_match(text: &str, pattern: &str) -> Option<(i32, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| if case_sensitive { c } else { fold_case(c) };
+    let text_chars: Vec<char> = text.chars().collect();
+    let pattern_chars: Vec<char> = pattern.chars().map(fold).collect();
+    let folded: Vec<char> = text_chars.iter().copied().map(fold).collect();
+    let (n, m) = (text_chars.len(), pattern_chars.len());
+    if m > n {
+        return None;
+    }
+
+    let bonus = bonuses(&text_chars);
 
-            // Beginning of word matches get bonus points
-            if text_idx == 0
-                || text_chars[text_idx - 1] == ' '
-                || text_chars[text_idx - 1] == '-'
-                || text_chars[text_idx - 1] == '_'
-            {
-                score += 15;
+    // score[i][j]: best score of pattern[..=i] within text[..=j], or None if
+    // it cannot be matched there. run[i][j]: length of the consecutive run
+    // ending with pattern[i] on text[j], 0 when text[j] is a gap.
+    let mut score = vec![vec![None::<i32>; n]; m];
+    let mut run = vec![vec![0usize; n]; m];
+
+    for i in 0..m {
+        let mut in_gap = false;
+        for j in 0..n {
+            let from_left = if j > 0 { score[i][j - 1] } else { None }.map(|left| {
+                left + if in_gap {
+                    SCORE_GAP_EXTENSION
+                } else {
+                    SCORE_GAP_START
+                }
+            });
+
+            let mut from_match = None;
+            let mut consecutive = 0;
+            if folded[j] == pattern_chars[i] {
+                if i == 0 {
+                    from_match = Some(SCORE_MATCH + bonus[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+                    consecutive = 1;
+                } else if let Some(diagonal) = if j > 0 { score[i - 1][j - 1] } else { None } {
+                    let mut b = bonus[j];
+                    consecutive = run[i - 1][j - 1] + 1;
+                    if consecutive > 1 {
+                        // A run keeps the bonus of the character it started
+                        // on, unless this character starts a stronger word.
+                        let first_bonus = bonus[j + 1 - consecutive];
+                        if b >= BONUS_BOUNDARY && b > first_bonus {
+                            consecutive = 1;
+                        } else {
+                            b = b.max(BONUS_CONSECUTIVE).max(first_bonus);
+                        }
+                    }
+                    from_match = Some(diagonal + SCORE_MATCH + b);
+                }
             }
 
-            last_match_idx = text_idx;
-            pattern_idx += 1;
+            // The first pattern character may always start over, so a poor
+            // early match never drags down a later one.
+            let best = match (from_match, from_left) {
+                (Some(matched), Some(left)) if i > 0 && left > matched => {
+                    run[i][j] = 0;
+                    in_gap = true;
+                    Some(left.max(0))
+                }
+                (Some(matched), _) => {
+                    run[i][j] = consecutive;
+                    in_gap = false;
+                    Some(matched)
+                }
+                (None, Some(left)) => {
+                    in_gap = true;
+                    Some(left.max(0))
+                }
+                (None, None) => None,
+            };
+            score[i][j] = best;
         }
     }
 
-    if pattern_idx == pattern_chars.len() {
-        // Shorter strings with matches score higher
-        score += 1000 / (text.len() + 1);
-        Some(score)
-    } else {
-        None
+    let (mut j, best) = score[m - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|&(j, s)| (s, std::cmp::Reverse(j)))?;
+
+    // Walk back from the best cell, preferring to stay on a consecutive run
+    // when a match and a gap score the same.
+    let mut positions = Vec::with_capacity(m);
+    let mut i = m - 1;
+    let mut prefer_match = true;
+    loop {
+        let current = score[i][j].unwrap_or(0);
+        let diagonal = if i > 0 && j > 0 {
+            score[i - 1][j - 1].unwrap_or(0)
+        } else {
+            0
+        };
+        let left = if j > 0 {
+            score[i][j - 1].unwrap_or(0)
+        } else {
+            0
+        };
+        let row = i;
+        if run[i][j] > 0
+            && current > diagonal
+            && (current > left || (current == left && prefer_match))
+        {
+            positions.push(j);
+            if i == 0 {
+                break;
+            }
+            i -= 1;
+        }
+        prefer_match = run[row][j] > 1 || (row + 1 < m && j + 1 < n && run[row + 1][j + 1] > 0);
+        if j == 0 {
+            return None;
+        }
+        j -= 1;
     }
+    positions.reverse();
+    Some((best, positions))
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::app::history::{now, Outcome, Visit};
     use super::*;
-    use ratatui::text::Line;
 
     #[test]
     fn test_fuzzy_match() {
@@ -82,21 +438,149 @@ mod tests {
         assert!(fuzzy_match("development-api", "dev").is_some());
         assert!(fuzzy_match("web-server", "web").is_some());
         assert!(fuzzy_match("database", "xyz").is_none());
+        assert_eq!(fuzzy_match("web-server", "ws").unwrap().1, vec![0, 4]);
+    }
+
+    #[test]
+    fn test_fuzzy_match_alignment() {
+        // The best alignment wins over the first occurrence.
+        assert_eq!(fuzzy_match("a_xb_ab", "ab").unwrap().1, vec![5, 6]);
+        assert_eq!(fuzzy_match("xaxa.b", "ab").unwrap().1, vec![3, 5]);
+        // Indices are characters, not bytes.
+        assert_eq!(fuzzy_match("Ärzte-Zentrum", "äz").unwrap().1, vec![0, 6]);
+        assert_eq!(fuzzy_match("über", "ub"), None);
+        // Smart case: an uppercase query character makes the match exact.
+        assert!(fuzzy_match("prod-db", "prod").is_some());
+        assert!(fuzzy_match("Prod-db", "prod").is_some());
+        assert!(fuzzy_match("Prod-db", "Prod").is_some());
+        assert!(fuzzy_match("prod-db", "Prod").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_ranking() {
+        let ranked = |query: &str, aliases: &[&str]| -> Vec<String> {
+            let items: Vec<SshHost> = aliases
+                .iter()
+                .map(|alias| SshHost::new(alias.to_string(), "config".into(), 1))
+                .collect();
+            fuzzy_search(&items, query, &History::default())
+                .into_iter()
+                .map(|(_, host, _)| host.alias)
+                .collect()
+        };
+
+        // Exact, then word start, then mid-word.
+        assert_eq!(
+            ranked("web", &["dewebber", "my-web-server", "web"]),
+            vec!["web", "my-web-server", "dewebber"]
+        );
+        // Word starts after '.', '/', '-' and camelCase humps.
+        assert_eq!(ranked("dbp", &["debug-prod", "db.prod"])[0], "db.prod");
+        assert_eq!(ranked("gc", &["magic", "getConfig"])[0], "getConfig");
+        assert_eq!(ranked("ep", &["deep", "eu/paris"])[0], "eu/paris");
+        // Digits start a new word.
+        assert_eq!(ranked("2", &["db12", "db2a"])[0], "db2a");
+        // Consecutive characters beat scattered ones.
+        assert_eq!(
+            ranked("prod", &["pxxrxxoxxd", "xproduction"])[0],
+            "xproduction"
+        );
+        // Equal scores fall back to the shorter name.
+        assert_eq!(
+            ranked("db", &["db-replica", "db"]),
+            vec!["db", "db-replica"]
+        );
     }
 
     #[test]
     fn test_fuzzy_search() {
-        let items = vec![
-            Line::from("production-server-01"),
-            Line::from("development-api"),
-            Line::from("web-server"),
-            Line::from("database-prod"),
-        ];
-
-        let results = fuzzy_search(&items, "prod");
+        let items: Vec<SshHost> = [
+            "production-server-01",
+            "development-api",
+            "web-server",
+            "database-prod",
+        ]
+        .iter()
+        .map(|alias| SshHost::new(alias.to_string(), "config".into(), 1))
+        .collect();
+
+        let results = fuzzy_search(&items, "prod", &History::default());
         assert!(!results.is_empty());
 
         // Should match both "production-server-01" and "database-prod"
         assert!(results.len() >= 2);
     }
+
+    #[test]
+    fn test_fuzzy_search_frecency() {
+        let dir = std::env::temp_dir().join("sshtui_fuzzy_frecency_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let items: Vec<SshHost> = ["web-01", "web-02", "webmail"]
+            .iter()
+            .map(|alias| SshHost::new(alias.to_string(), "config".into(), 1))
+            .collect();
+        let mut history = History::load_from(dir.join("history.tsv")).unwrap();
+        history
+            .record(Visit {
+                timestamp: now(),
+                alias: "web-02".to_string(),
+                config_file: "config".into(),
+                launcher: "foreground".to_string(),
+                outcome: Outcome::Exited(0),
+                duration: 60,
+            })
+            .unwrap();
+
+        let aliases = |query: &str| -> Vec<String> {
+            fuzzy_search(&items, query, &history)
+                .into_iter()
+                .map(|(_, host, _)| host.alias)
+                .collect()
+        };
+        assert_eq!(aliases("web"), vec!["web-02", "web-01", "webmail"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fuzzy_search_host_fields() {
+        let mut db = SshHost::new("db".to_string(), "config".into(), 1);
+        db.hostname = Some("10.20.30.40".to_string());
+        db.user = Some("postgres".to_string());
+        db.comments = vec!["Billing replica".to_string()];
+        let mut web = SshHost::new("web".to_string(), "config".into(), 5);
+        web.user = Some("root".to_string());
+        web.tags = vec!["frontend".to_string()];
+        let items = vec![db, web];
+
+        let field = |query: &str| {
+            let results = fuzzy_search(&items, query, &History::default());
+            assert_eq!(results.len(), 1, "{}", query);
+            (results[0].1.alias.clone(), results[0].2.field)
+        };
+        assert_eq!(field("10.20.30"), ("db".to_string(), HostField::HostName));
+        assert_eq!(field("postg"), ("db".to_string(), HostField::User));
+        assert_eq!(field("billing"), ("db".to_string(), HostField::Comment));
+        assert_eq!(field("root"), ("web".to_string(), HostField::User));
+        assert_eq!(field("front"), ("web".to_string(), HostField::Tag));
+        assert_eq!(field("we"), ("web".to_string(), HostField::Alias));
+        assert_eq!(
+            fuzzy_search(&items, "Bill", &History::default())[0].2.positions,
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_filter_files() {
+        let files: Vec<FileEntry> = ["paris-prod", "work", "personal"]
+            .iter()
+            .map(|name| FileEntry::new(name.to_string(), name.into()))
+            .collect();
+
+        let results = fuzzy_filter_files(&files, "pp");
+        let names: Vec<&str> = results.iter().map(|(_, f, _)| f.name.as_str()).collect();
+        assert_eq!(names, vec!["paris-prod"]);
+        assert_eq!(results[0].2, vec![0, 6]);
+        assert_eq!(fuzzy_filter_files(&files, "wk")[0].0, 1);
+    }
 }
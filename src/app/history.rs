@@ -0,0 +1,370 @@
+use super::backup::write_file;
+use super::parser::SshHost;
+use super::paths::home_dir;
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const HISTORY_FILE_NAME: &str = "history.tsv";
+
+/// How much frecency counts against fuzzy match quality. The boost grows with
+/// the logarithm of the frecency so a daily host gains a few matched
+/// characters' worth of score, not enough to beat a clearly better match.
+const FRECENCY_WEIGHT: f64 = 8.0;
+
+/// Where the application keeps its own data between runs:
+/// `$XDG_DATA_HOME/sshtui`, `~/.local/share/sshtui` or
+/// `%LOCALAPPDATA%\sshtui` on Windows.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".local").join("share")))
+    };
+    base.map(|base| base.join("sshtui"))
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// How a connection attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Handed to a launcher that does not wait for ssh to exit.
+    Launched,
+    /// ssh exited with this code.
+    Exited(i32),
+    /// The launcher could not start ssh, or ssh was killed.
+    Failed,
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Launched | Outcome::Exited(0))
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Launched => write!(f, "launched"),
+            Outcome::Exited(code) => write!(f, "exit {}", code),
+            Outcome::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "launched" => Ok(Outcome::Launched),
+            "failed" => Ok(Outcome::Failed),
+            _ => value
+                .strip_prefix("exit ")
+                .and_then(|code| code.parse().ok())
+                .map(Outcome::Exited)
+                .ok_or(()),
+        }
+    }
+}
+
+/// One connection attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Visit {
+    pub timestamp: u64,
+    pub alias: String,
+    /// The config file ssh was given with `-F`.
+    pub config_file: PathBuf,
+    /// Name of the launcher that ran ssh.
+    pub launcher: String,
+    pub outcome: Outcome,
+    /// How long the launcher ran, in seconds.
+    pub duration: u64,
+}
+
+impl Visit {
+    /// Reads a `timestamp, alias, config file, launcher, outcome, duration`
+    /// line of tab separated fields. Lines written before the last three
+    /// fields existed count as launched.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let timestamp = fields.next()?.parse().ok()?;
+        let alias = fields.next().filter(|alias| !alias.is_empty())?;
+        let config_file = fields.next()?;
+        let launcher = fields.next().unwrap_or_default();
+        let outcome = match fields.next() {
+            Some(outcome) => outcome.parse().ok()?,
+            None => Outcome::Launched,
+        };
+        let duration = match fields.next() {
+            Some(duration) => duration.parse().ok()?,
+            None => 0,
+        };
+        Some(Visit {
+            timestamp,
+            alias: alias.to_string(),
+            config_file: PathBuf::from(config_file),
+            launcher: launcher.to_string(),
+            outcome,
+            duration,
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.timestamp,
+            self.alias,
+            self.config_file.display(),
+            self.launcher,
+            self.outcome,
+            self.duration
+        )
+    }
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    // Days to civil date, from Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Formats a duration in seconds like `45s`, `12m` or `3h05m`.
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+/// Weight of a visit by its age in seconds, in the spirit of Firefox's
+/// frecency buckets: recent visits count for more than old ones.
+fn visit_weight(age: u64) -> u32 {
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+    match age {
+        age if age < 4 * HOUR => 100,
+        age if age < DAY => 80,
+        age if age < 7 * DAY => 60,
+        age if age < 30 * DAY => 40,
+        age if age < 90 * DAY => 20,
+        _ => 10,
+    }
+}
+
+/// Past connection attempts, kept in a tab separated file with one visit per
+/// line, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    /// The history file, `None` when history is not persisted.
+    path: Option<PathBuf>,
+    visits: Vec<Visit>,
+    /// Frecency of each (alias, config file) from its successful visits, kept
+    /// up to date with `visits`.
+    frecency: HashMap<(String, PathBuf), u32>,
+}
+
+impl History {
+    /// Loads the history from the data directory. A missing file is an empty
+    /// history.
+    pub fn load() -> io::Result<Self> {
+        match data_dir() {
+            Some(dir) => Self::load_from(dir.join(HISTORY_FILE_NAME)),
+            None => Ok(History::default()),
+        }
+    }
+
+    /// Loads the history kept in `path`. Lines that cannot be read are
+    /// skipped.
+    pub fn load_from(path: PathBuf) -> io::Result<Self> {
+        let visits = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().filter_map(Visit::parse).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut history = History {
+            path: Some(path),
+            visits,
+            frecency: HashMap::new(),
+        };
+        history.update_frecency(now());
+        Ok(history)
+    }
+
+    pub fn visits(&self) -> &[Visit] {
+        &self.visits
+    }
+
+    /// Adds a connection attempt, appending it to the history file.
+    pub fn record(&mut self, visit: Visit) -> io::Result<()> {
+        let line = visit.to_line();
+        self.visits.push(visit);
+        self.update_frecency(now());
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Replaces the last visit recorded, rewriting the history file.
+    pub fn replace_last(&mut self, visit: Visit) -> io::Result<()> {
+        match self.visits.last_mut() {
+            Some(last) => *last = visit,
+            None => self.visits.push(visit),
+        }
+        self.update_frecency(now());
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut content = String::new();
+        for visit in &self.visits {
+            content.push_str(&visit.to_line());
+            content.push('\n');
+        }
+        write_file(path, &content)
+    }
+
+    fn update_frecency(&mut self, now: u64) {
+        self.frecency.clear();
+        for visit in self.visits.iter().filter(|visit| visit.outcome.is_success()) {
+            *self
+                .frecency
+                .entry((visit.alias.clone(), visit.config_file.clone()))
+                .or_default() += visit_weight(now.saturating_sub(visit.timestamp));
+        }
+    }
+
+    /// How often and how recently `host` was connected to.
+    pub fn frecency(&self, host: &SshHost) -> u32 {
+        self.frecency
+            .get(&(host.alias.clone(), host.config_file.clone()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Score added to a host's search score for its frecency.
+    pub fn boost(&self, host: &SshHost) -> i32 {
+        (f64::from(self.frecency(host)).ln_1p() * FRECENCY_WEIGHT) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(alias: &str, outcome: Outcome) -> Visit {
+        Visit {
+            timestamp: now(),
+            alias: alias.to_string(),
+            config_file: PathBuf::from("/etc/ssh/work"),
+            launcher: "tmux".to_string(),
+            outcome,
+            duration: 42,
+        }
+    }
+
+    #[test]
+    fn test_record_and_reload() {
+        let dir = std::env::temp_dir().join("sshtui_history_test");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join(HISTORY_FILE_NAME);
+
+        let mut history = History::load_from(path.clone()).unwrap();
+        assert!(history.visits.is_empty());
+        history.record(visit("web", Outcome::Exited(0))).unwrap();
+        history.record(visit("web", Outcome::Launched)).unwrap();
+        history.record(visit("db", Outcome::Exited(255))).unwrap();
+        history.record(visit("db", Outcome::Failed)).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let old_format = format!("{}\tdb\t/etc/ssh/work\n", now());
+        fs::write(&path, written + "garbage line\n" + &old_format).unwrap();
+
+        let reloaded = History::load_from(path).unwrap();
+        assert_eq!(reloaded.visits()[..4], history.visits[..]);
+        assert_eq!(reloaded.visits()[4].outcome, Outcome::Launched);
+        assert_eq!(reloaded.visits().len(), 5);
+        let web = SshHost::new("web".to_string(), "/etc/ssh/work".into(), 1);
+        let db = SshHost::new("db".to_string(), "/etc/ssh/work".into(), 5);
+        let elsewhere = SshHost::new("web".to_string(), "/etc/ssh/home".into(), 1);
+        assert_eq!(reloaded.frecency(&web), 200);
+        assert_eq!(reloaded.frecency(&db), 100);
+        assert_eq!(reloaded.frecency(&elsewhere), 0);
+        assert!(reloaded.boost(&web) > reloaded.boost(&db));
+        assert_eq!(reloaded.boost(&elsewhere), 0);
+
+        // A visit recorded before exec is corrected when ssh fails to start.
+        let mut reloaded = reloaded;
+        reloaded.record(visit("web", Outcome::Launched)).unwrap();
+        reloaded
+            .replace_last(visit("web", Outcome::Failed))
+            .unwrap();
+        assert_eq!(reloaded.frecency(&web), 200);
+        let reread = History::load_from(reloaded.path.clone().unwrap()).unwrap();
+        assert_eq!(reread.visits(), reloaded.visits());
+        assert_eq!(reread.visits().last().unwrap().outcome, Outcome::Failed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_827_696), "2000-02-29 12:34");
+        assert_eq!(format_timestamp(1_792_368_000), "2026-10-19 00:00");
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(754), "12m");
+        assert_eq!(format_duration(11_100), "3h05m");
+    }
+
+    #[test]
+    fn test_visit_weight() {
+        assert_eq!(visit_weight(0), 100);
+        assert_eq!(visit_weight(5 * 60 * 60), 80);
+        assert_eq!(visit_weight(3 * 24 * 60 * 60), 60);
+        assert_eq!(visit_weight(365 * 24 * 60 * 60), 10);
+    }
+}
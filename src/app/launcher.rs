@@ -0,0 +1,605 @@
+//! Ways of opening an ssh session for the selected host: a new terminal tab,
+//! a multiplexer window, or the terminal the TUI runs in.
+
+use super::pattern::wildcard_match;
+use super::settings::LauncherSettings;
+use std::{
+    env, fmt,
+    io::{self, Error, ErrorKind},
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+    thread,
+};
+
+mod terminal;
+
+use terminal::{Alacritty, Foot, GnomeTerminal, Kitty, Wezterm, WindowsTerminal};
+
+/// The host to connect to and the config file ssh should read it from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchTarget {
+    pub alias: String,
+    pub config_file: PathBuf,
+}
+
+impl LaunchTarget {
+    /// `ssh -F <config> -- <alias>` as separate arguments. The `--` keeps an
+    /// alias starting with `-` from being read as an option.
+    pub fn ssh_args(&self) -> Vec<String> {
+        vec![
+            "ssh".to_string(),
+            "-F".to_string(),
+            self.config_file.to_string_lossy().into_owned(),
+            "--".to_string(),
+            self.alias.clone(),
+        ]
+    }
+
+    /// The ssh invocation as a single command line for launchers that hand
+    /// it to a shell, every argument quoted for it.
+    pub fn ssh_command(&self) -> String {
+        let args: Vec<String> = self.ssh_args().iter().map(|arg| shell_quote(arg)).collect();
+        args.join(" ")
+    }
+}
+
+/// `arg` quoted so a shell reads it as one word with nothing expanded: in
+/// single quotes for sh, in double quotes for cmd on Windows. Plain words are
+/// left as they are.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "@_+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    if cfg!(windows) {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchReport {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub message: String,
+}
+
+pub trait Launcher {
+    fn name(&self) -> &'static str;
+
+    /// Program and arguments that open the session.
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>>;
+
+    /// Launchers that run the session in the TUI's own terminal need it
+    /// handed over for the duration of the launch.
+    fn takes_over_terminal(&self) -> bool {
+        false
+    }
+
+    /// Launchers that start a new terminal window keep running for as long
+    /// as the session does, so they are not waited for.
+    fn spawns_window(&self) -> bool {
+        false
+    }
+
+    /// Launchers that replace this process with ssh only return when ssh
+    /// could not be started.
+    fn replaces_process(&self) -> bool {
+        false
+    }
+
+    fn launch(&self, target: &LaunchTarget) -> io::Result<LaunchReport> {
+        let command = self.command(target)?;
+        if self.takes_over_terminal() {
+            run_foreground(&command, target, self.name())
+        } else if self.spawns_window() {
+            run_spawned(&command, target, self.name())
+        } else {
+            run_detached(&command, target, self.name())
+        }
+    }
+}
+
+fn build(command: &[String]) -> io::Result<Command> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "empty launcher command"))?;
+    let mut process = Command::new(program);
+    process.args(args);
+    Ok(process)
+}
+
+/// Runs a launcher that opens the session somewhere else and returns once
+/// it has done so.
+fn run_detached(command: &[String], target: &LaunchTarget, name: &str) -> io::Result<LaunchReport> {
+    let output = build(command)?
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+
+    let stderr_msg = String::from_utf8_lossy(&output.stderr);
+    let stdout_msg = String::from_utf8_lossy(&output.stdout);
+    let message = if output.status.success() {
+        format!("Connected to: {} ({})", target.alias, name)
+    } else {
+        format!(
+            "Failed to connect to: {} ({})\nStdout: {}\nStderr: {}",
+            target.alias, name, stdout_msg, stderr_msg
+        )
+    };
+    Ok(LaunchReport {
+        success: output.status.success(),
+        exit_code: output.status.code(),
+        message,
+    })
+}
+
+/// Starts a launcher that lives as long as the session, reaping it in the
+/// background once it exits.
+fn run_spawned(command: &[String], target: &LaunchTarget, name: &str) -> io::Result<LaunchReport> {
+    let mut child = build(command)?
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(LaunchReport {
+        success: true,
+        exit_code: None,
+        message: format!("Opened {} in a new {} window", target.alias, name),
+    })
+}
+
+/// Runs a launcher attached to the current terminal until it exits.
+fn run_foreground(
+    command: &[String],
+    target: &LaunchTarget,
+    name: &str,
+) -> io::Result<LaunchReport> {
+    let status = build(command)?.status()?;
+    let exit = status
+        .code()
+        .map(|code| format!("exit code {}", code))
+        .unwrap_or_else(|| "terminated by a signal".to_string());
+    Ok(LaunchReport {
+        success: status.success(),
+        exit_code: status.code(),
+        message: format!("Session to {} ended ({}, {})", target.alias, name, exit),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LauncherKind {
+    /// Picks a launcher from the environment.
+    Auto,
+    WindowsTerminal,
+    Tmux,
+    Screen,
+    Zellij,
+    Kitty,
+    Wezterm,
+    Alacritty,
+    GnomeTerminal,
+    Foot,
+    /// Runs ssh in the current terminal and returns to the TUI afterwards.
+    Foreground,
+    /// Replaces the TUI with ssh in the current terminal.
+    Exec,
+    /// The `command` template from the settings.
+    Custom,
+}
+
+impl FromStr for LauncherKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "wt" | "windows-terminal" => Ok(Self::WindowsTerminal),
+            "tmux" => Ok(Self::Tmux),
+            "screen" => Ok(Self::Screen),
+            "zellij" => Ok(Self::Zellij),
+            "kitty" => Ok(Self::Kitty),
+            "wezterm" => Ok(Self::Wezterm),
+            "alacritty" => Ok(Self::Alacritty),
+            "gnome-terminal" => Ok(Self::GnomeTerminal),
+            "foot" => Ok(Self::Foot),
+            "foreground" => Ok(Self::Foreground),
+            "exec" => Ok(Self::Exec),
+            "custom" => Ok(Self::Custom),
+            _ => Err(format!(
+                "unknown launcher '{}', expected one of auto, wt, tmux, screen, zellij, \
+                 kitty, wezterm, alacritty, gnome-terminal, foot, foreground, exec, custom",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LauncherKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Auto => "auto",
+            Self::WindowsTerminal => "wt",
+            Self::Tmux => "tmux",
+            Self::Screen => "screen",
+            Self::Zellij => "zellij",
+            Self::Kitty => "kitty",
+            Self::Wezterm => "wezterm",
+            Self::Alacritty => "alacritty",
+            Self::GnomeTerminal => "gnome-terminal",
+            Self::Foot => "foot",
+            Self::Foreground => "foreground",
+            Self::Exec => "exec",
+            Self::Custom => "custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where tmux opens the session when the TUI already runs inside tmux.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TmuxMode {
+    Window,
+    Split,
+}
+
+impl FromStr for TmuxMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "window" => Ok(Self::Window),
+            "split" => Ok(Self::Split),
+            _ => Err(format!(
+                "unknown tmux mode '{}', expected window or split",
+                name
+            )),
+        }
+    }
+}
+
+fn is_set(var: &str) -> bool {
+    env::var_os(var).is_some_and(|value| !value.is_empty())
+}
+
+/// Chooses the launcher for `alias`: the first matching per-host rule from
+/// the settings, otherwise the configured kind.
+pub fn for_host(settings: &LauncherSettings, alias: &str) -> Box<dyn Launcher> {
+    let kind = settings
+        .hosts
+        .iter()
+        .find(|(pattern, _)| wildcard_match(pattern, alias))
+        .map(|(_, kind)| *kind)
+        .unwrap_or(settings.kind);
+    from_kind(kind, settings)
+}
+
+fn from_kind(kind: LauncherKind, settings: &LauncherSettings) -> Box<dyn Launcher> {
+    match kind {
+        LauncherKind::Auto => from_kind(detect(), settings),
+        LauncherKind::WindowsTerminal => Box::new(WindowsTerminal {
+            program: "wt".to_string(),
+        }),
+        LauncherKind::Tmux => Box::new(Tmux {
+            inside: is_set("TMUX"),
+            mode: settings.tmux_mode,
+        }),
+        LauncherKind::Screen => Box::new(Screen {
+            inside: is_set("STY"),
+        }),
+        LauncherKind::Zellij => Box::new(Zellij {
+            inside: is_set("ZELLIJ"),
+        }),
+        LauncherKind::Kitty => Box::new(Kitty {
+            program: "kitty".to_string(),
+            remote_control: is_set("KITTY_LISTEN_ON"),
+        }),
+        LauncherKind::Wezterm => Box::new(Wezterm {
+            program: "wezterm".to_string(),
+            inside: is_set("WEZTERM_PANE"),
+        }),
+        LauncherKind::Alacritty => Box::new(Alacritty {
+            program: "alacritty".to_string(),
+            inside: is_set("ALACRITTY_SOCKET"),
+        }),
+        LauncherKind::GnomeTerminal => Box::new(GnomeTerminal {
+            program: "gnome-terminal".to_string(),
+        }),
+        LauncherKind::Foot => Box::new(Foot {
+            program: "foot".to_string(),
+        }),
+        LauncherKind::Foreground => Box::new(Foreground),
+        LauncherKind::Exec => Box::new(Exec),
+        LauncherKind::Custom => Box::new(Custom {
+            template: settings.command.clone(),
+        }),
+    }
+}
+
+/// Prefers the multiplexer the TUI is running in, then the terminal emulator
+/// it is running in, then Windows Terminal on Windows, and otherwise runs ssh
+/// in the current terminal.
+fn detect() -> LauncherKind {
+    if is_set("TMUX") {
+        LauncherKind::Tmux
+    } else if is_set("ZELLIJ") {
+        LauncherKind::Zellij
+    } else if is_set("STY") {
+        LauncherKind::Screen
+    } else if let Some(kind) = terminal::detect() {
+        kind
+    } else if cfg!(windows) {
+        LauncherKind::WindowsTerminal
+    } else {
+        LauncherKind::Foreground
+    }
+}
+
+/// Inside tmux, opens a new window or split pane. Outside, starts or attaches
+/// a session named after the host in the current terminal.
+pub struct Tmux {
+    pub inside: bool,
+    pub mode: TmuxMode,
+}
+
+impl Launcher for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = vec!["tmux".to_string()];
+        match (self.inside, self.mode) {
+            (true, TmuxMode::Window) => {
+                command.extend(["new-window".to_string(), "-n".to_string()]);
+                command.push(target.alias.clone());
+            }
+            (true, TmuxMode::Split) => command.push("split-window".to_string()),
+            (false, _) => {
+                // `-A` attaches when a session for the host already exists.
+                command.extend([
+                    "new-session".to_string(),
+                    "-A".to_string(),
+                    "-s".to_string(),
+                ]);
+                command.push(target.alias.replace(['.', ':'], "_"));
+            }
+        }
+        command.extend(target.ssh_args());
+        Ok(command)
+    }
+
+    fn takes_over_terminal(&self) -> bool {
+        !self.inside
+    }
+}
+
+/// Inside GNU screen, opens a new window. Outside, starts a session in the
+/// current terminal.
+pub struct Screen {
+    pub inside: bool,
+}
+
+impl Launcher for Screen {
+    fn name(&self) -> &'static str {
+        "screen"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let flag = if self.inside { "-t" } else { "-S" };
+        let mut command = vec!["screen".to_string(), flag.to_string(), target.alias.clone()];
+        command.extend(target.ssh_args());
+        Ok(command)
+    }
+
+    fn takes_over_terminal(&self) -> bool {
+        !self.inside
+    }
+}
+
+/// Opens a new pane in the running zellij session.
+pub struct Zellij {
+    pub inside: bool,
+}
+
+impl Launcher for Zellij {
+    fn name(&self) -> &'static str {
+        "zellij"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        if !self.inside {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the zellij launcher only works inside a zellij session",
+            ));
+        }
+        let mut command: Vec<String> = ["zellij", "run", "--close-on-exit", "--name"]
+            .map(String::from)
+            .to_vec();
+        command.push(target.alias.clone());
+        command.push("--".to_string());
+        command.extend(target.ssh_args());
+        Ok(command)
+    }
+}
+
+/// Runs ssh in the terminal the TUI was drawn in, for machines without a
+/// graphical terminal. The TUI comes back when the session ends.
+pub struct Foreground;
+
+impl Launcher for Foreground {
+    fn name(&self) -> &'static str {
+        "foreground"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        Ok(target.ssh_args())
+    }
+
+    fn takes_over_terminal(&self) -> bool {
+        true
+    }
+}
+
+/// Replaces the TUI with ssh: the application ends and ssh keeps the
+/// terminal.
+pub struct Exec;
+
+impl Launcher for Exec {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        Ok(target.ssh_args())
+    }
+
+    fn takes_over_terminal(&self) -> bool {
+        true
+    }
+
+    fn replaces_process(&self) -> bool {
+        true
+    }
+
+    fn launch(&self, target: &LaunchTarget) -> io::Result<LaunchReport> {
+        let command = self.command(target)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // Only returns if ssh could not be started.
+            Err(build(&command)?.exec())
+        }
+        #[cfg(not(unix))]
+        {
+            let status = build(&command)?.status()?;
+            std::process::exit(status.code().unwrap_or(1));
+        }
+    }
+}
+
+/// Runs the `command` template from the settings, substituting `{ssh}`,
+/// `{alias}` and `{config}`.
+pub struct Custom {
+    pub template: Vec<String>,
+}
+
+impl Launcher for Custom {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let ssh_command = target.ssh_command();
+        let config = target.config_file.to_string_lossy();
+        Ok(self
+            .template
+            .iter()
+            .map(|arg| {
+                arg.replace("{ssh}", &ssh_command)
+                    .replace("{alias}", &target.alias)
+                    .replace("{config}", &config)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> LaunchTarget {
+        LaunchTarget {
+            alias: "db.prod".to_string(),
+            config_file: PathBuf::from("/home/me/.ssh/config"),
+        }
+    }
+
+    fn command(launcher: &dyn Launcher) -> String {
+        launcher.command(&target()).unwrap().join(" ")
+    }
+
+    #[test]
+    fn test_launcher_commands() {
+        let ssh = "ssh -F /home/me/.ssh/config -- db.prod";
+        assert_eq!(
+            command(&WindowsTerminal {
+                program: "wt".to_string()
+            }),
+            format!("wt -w 0 new-tab --title db.prod cmd /k {}", ssh)
+        );
+        let tmux = |inside, mode| command(&Tmux { inside, mode });
+        assert_eq!(
+            tmux(true, TmuxMode::Window),
+            format!("tmux new-window -n db.prod {}", ssh)
+        );
+        assert_eq!(
+            tmux(true, TmuxMode::Split),
+            format!("tmux split-window {}", ssh)
+        );
+        assert_eq!(
+            tmux(false, TmuxMode::Window),
+            format!("tmux new-session -A -s db_prod {}", ssh)
+        );
+        assert_eq!(
+            command(&Screen { inside: true }),
+            format!("screen -t db.prod {}", ssh)
+        );
+        assert_eq!(
+            command(&Zellij { inside: true }),
+            format!("zellij run --close-on-exit --name db.prod -- {}", ssh)
+        );
+        assert!(Zellij { inside: false }.command(&target()).is_err());
+        assert_eq!(command(&Exec), ssh);
+        assert_eq!(command(&Foreground), ssh);
+        assert!(Foreground.takes_over_terminal());
+
+        let custom = Custom {
+            template: vec!["term".into(), "--title={alias}".into(), "{ssh}".into()],
+        };
+        assert_eq!(
+            custom.command(&target()).unwrap(),
+            vec!["term", "--title=db.prod", ssh]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ssh_command_quoting() {
+        let target = LaunchTarget {
+            alias: "a;b`c`".to_string(),
+            config_file: PathBuf::from("/tmp/it's $(x)/config"),
+        };
+        let command = target.ssh_command();
+        assert_eq!(command, "ssh -F '/tmp/it'\\''s $(x)/config' -- 'a;b`c`'");
+        // The shell gives ssh back the arguments as they were.
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s\\n' {}", command))
+            .output()
+            .unwrap();
+        let args: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(args, target.ssh_args());
+    }
+
+    #[test]
+    fn test_for_host_rules() {
+        let settings = LauncherSettings {
+            kind: LauncherKind::Exec,
+            hosts: vec![("*.prod".to_string(), LauncherKind::WindowsTerminal)],
+            ..LauncherSettings::default()
+        };
+        assert_eq!(for_host(&settings, "db.prod").name(), "Windows Terminal");
+        assert_eq!(for_host(&settings, "web").name(), "exec");
+        assert!(for_host(&settings, "web").takes_over_terminal());
+    }
+}
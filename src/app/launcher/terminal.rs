@@ -0,0 +1,299 @@
+//! Terminal emulators that can open the session in a new tab or window.
+
+use super::{LaunchTarget, Launcher, LauncherKind};
+use std::{env, io};
+
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Recognises the terminal emulator the TUI runs in from the variables each
+/// of them exports.
+pub fn detect() -> Option<LauncherKind> {
+    let term = var("TERM").unwrap_or_default();
+    let term_program = var("TERM_PROGRAM").unwrap_or_default();
+
+    if var("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" {
+        Some(LauncherKind::Kitty)
+    } else if var("WEZTERM_PANE").is_some() || term_program == "WezTerm" {
+        Some(LauncherKind::Wezterm)
+    } else if var("ALACRITTY_WINDOW_ID").is_some() || var("ALACRITTY_SOCKET").is_some() {
+        Some(LauncherKind::Alacritty)
+    } else if term.starts_with("foot") {
+        Some(LauncherKind::Foot)
+    } else if var("GNOME_TERMINAL_SCREEN").is_some() || var("GNOME_TERMINAL_SERVICE").is_some() {
+        Some(LauncherKind::GnomeTerminal)
+    } else if var("WT_SESSION").is_some() {
+        Some(LauncherKind::WindowsTerminal)
+    } else {
+        None
+    }
+}
+
+fn with_ssh(mut command: Vec<String>, target: &LaunchTarget) -> Vec<String> {
+    command.extend(target.ssh_args());
+    command
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Opens a new tab in the current Windows Terminal window. `cmd /k` keeps the
+/// tab open after ssh exits so errors stay readable.
+pub struct WindowsTerminal {
+    pub program: String,
+}
+
+impl Launcher for WindowsTerminal {
+    fn name(&self) -> &'static str {
+        "Windows Terminal"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = args(&[&self.program, "-w", "0", "new-tab", "--title"]);
+        command.push(target.alias.clone());
+        command.extend(args(&["cmd", "/k"]));
+        command.push(target.ssh_command());
+        Ok(command)
+    }
+}
+
+/// Inside kitty with remote control enabled, opens a tab in the current
+/// window. Otherwise starts a new kitty window.
+pub struct Kitty {
+    pub program: String,
+    pub remote_control: bool,
+}
+
+impl Launcher for Kitty {
+    fn name(&self) -> &'static str {
+        "kitty"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = if self.remote_control {
+            args(&[&self.program, "@", "launch", "--type=tab", "--tab-title"])
+        } else {
+            args(&[&self.program, "--title"])
+        };
+        command.push(target.alias.clone());
+        Ok(with_ssh(command, target))
+    }
+
+    fn spawns_window(&self) -> bool {
+        !self.remote_control
+    }
+}
+
+/// Inside WezTerm, opens a tab through its multiplexer. Otherwise starts a
+/// new WezTerm window.
+pub struct Wezterm {
+    pub program: String,
+    pub inside: bool,
+}
+
+impl Launcher for Wezterm {
+    fn name(&self) -> &'static str {
+        "WezTerm"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let command = if self.inside {
+            args(&[&self.program, "cli", "spawn", "--"])
+        } else {
+            args(&[&self.program, "start", "--"])
+        };
+        Ok(with_ssh(command, target))
+    }
+
+    fn spawns_window(&self) -> bool {
+        !self.inside
+    }
+}
+
+/// Alacritty has no tabs: inside Alacritty a window is added to the running
+/// instance, otherwise a new instance is started.
+pub struct Alacritty {
+    pub program: String,
+    pub inside: bool,
+}
+
+impl Launcher for Alacritty {
+    fn name(&self) -> &'static str {
+        "Alacritty"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = if self.inside {
+            args(&[&self.program, "msg", "create-window", "--title"])
+        } else {
+            args(&[&self.program, "--title"])
+        };
+        command.push(target.alias.clone());
+        command.push("-e".to_string());
+        Ok(with_ssh(command, target))
+    }
+
+    fn spawns_window(&self) -> bool {
+        !self.inside
+    }
+}
+
+/// Opens a tab in the running GNOME Terminal server, which returns as soon as
+/// the tab exists.
+pub struct GnomeTerminal {
+    pub program: String,
+}
+
+impl Launcher for GnomeTerminal {
+    fn name(&self) -> &'static str {
+        "GNOME Terminal"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = args(&[&self.program, "--tab"]);
+        command.push(format!("--title={}", target.alias));
+        command.push("--".to_string());
+        Ok(with_ssh(command, target))
+    }
+}
+
+/// Starts a new foot window.
+pub struct Foot {
+    pub program: String,
+}
+
+impl Launcher for Foot {
+    fn name(&self) -> &'static str {
+        "foot"
+    }
+
+    fn command(&self, target: &LaunchTarget) -> io::Result<Vec<String>> {
+        let mut command = args(&[&self.program, "--title"]);
+        command.push(target.alias.clone());
+        Ok(with_ssh(command, target))
+    }
+
+    fn spawns_window(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf, thread, time::Duration};
+
+    fn target() -> LaunchTarget {
+        LaunchTarget {
+            alias: "web".to_string(),
+            config_file: PathBuf::from("/cfg"),
+        }
+    }
+
+    fn command(launcher: &dyn Launcher) -> String {
+        launcher.command(&target()).unwrap().join(" ")
+    }
+
+    #[test]
+    fn test_terminal_commands() {
+        let program = |name: &str| name.to_string();
+        assert_eq!(
+            command(&Kitty {
+                program: program("kitty"),
+                remote_control: true
+            }),
+            "kitty @ launch --type=tab --tab-title web ssh -F /cfg -- web"
+        );
+        assert_eq!(
+            command(&Kitty {
+                program: program("kitty"),
+                remote_control: false
+            }),
+            "kitty --title web ssh -F /cfg -- web"
+        );
+        assert_eq!(
+            command(&Wezterm {
+                program: program("wezterm"),
+                inside: true
+            }),
+            "wezterm cli spawn -- ssh -F /cfg -- web"
+        );
+        assert_eq!(
+            command(&Alacritty {
+                program: program("alacritty"),
+                inside: true
+            }),
+            "alacritty msg create-window --title web -e ssh -F /cfg -- web"
+        );
+        assert_eq!(
+            command(&GnomeTerminal {
+                program: program("gnome-terminal")
+            }),
+            "gnome-terminal --tab --title=web -- ssh -F /cfg -- web"
+        );
+        assert_eq!(
+            command(&Foot {
+                program: program("foot")
+            }),
+            "foot --title web ssh -F /cfg -- web"
+        );
+    }
+
+    /// Runs each launcher against a script that records the arguments it was
+    /// started with, so the real argv is checked rather than a rendering.
+    #[cfg(unix)]
+    #[test]
+    fn test_terminal_launch_with_fake_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = env::temp_dir().join("test_launcher_fake_terminal");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let fake = temp_dir.join("fake-terminal");
+        let record = temp_dir.join("args");
+        fs::write(
+            &fake,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"{}.tmp\"\nmv \"{0}.tmp\" \"{0}\"\n",
+                record.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+        let program = fake.to_string_lossy().into_owned();
+
+        let launchers: Vec<(Box<dyn Launcher>, &str)> = vec![
+            (
+                Box::new(GnomeTerminal {
+                    program: program.clone(),
+                }),
+                "--tab\n--title=web\n--\nssh\n-F\n/cfg\n--\nweb\n",
+            ),
+            (
+                Box::new(Foot {
+                    program: program.clone(),
+                }),
+                "--title\nweb\nssh\n-F\n/cfg\n--\nweb\n",
+            ),
+        ];
+        for (launcher, expected) in launchers {
+            let _ = fs::remove_file(&record);
+            let report = launcher.launch(&target()).unwrap();
+            assert!(report.success, "{}", report.message);
+
+            // Windowed launchers do not wait for the terminal to exit.
+            let mut recorded = None;
+            for _ in 0..100 {
+                if let Ok(args) = fs::read_to_string(&record) {
+                    recorded = Some(args);
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            assert_eq!(recorded.as_deref(), Some(expected), "{}", launcher.name());
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use super::parser::{get_files_in_folder, FileEntry};
+use super::paths::{expand_tilde, home_dir, ssh_dir};
+use super::settings::Settings;
+use std::{env, fmt, path::PathBuf};
+
+pub const CONFIG_DIR_ENV: &str = "SSHTUI_CONFIG_DIR";
+pub const CONFIG_FILE_ENV: &str = "SSHTUI_CONFIG_FILE";
+
+pub const USAGE: &str = "Usage: essaysahTui [--config-file <file>]... [--config-dir <dir>]...
+
+Options:
+  --config-file <file>  SSH config file to list, may be repeated
+  --config-dir <dir>    Folder whose files are listed, may be repeated
+  -h, --help            Print this help
+
+Without options the SSHTUI_CONFIG_FILE and SSHTUI_CONFIG_DIR environment
+variables are used, then the [paths] of settings.toml, then ~/.ssh/config
+and ~/.ssh/config.d.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    CommandLine,
+    Environment,
+    Settings,
+    Default,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::CommandLine => write!(f, "command line"),
+            Origin::Environment => write!(f, "environment"),
+            Origin::Settings => write!(f, "settings"),
+            Origin::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Where SSH config files are looked for: individual files plus folders
+/// whose files are all listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLocations {
+    pub files: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+    pub origin: Origin,
+}
+
+impl ConfigLocations {
+    /// Command line arguments win over the environment, which wins over the
+    /// settings file and then the `~/.ssh` defaults. `env` looks up an
+    /// environment variable.
+    pub fn resolve<I, F>(args: I, env: F, settings: &Settings) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let target = match flag.as_str() {
+                "--config-file" => &mut files,
+                "--config-dir" => &mut dirs,
+                _ => return Err(format!("Unknown argument '{}'\n\n{}", arg, USAGE)),
+            };
+            let value = inline_value
+                .or_else(|| args.next())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| format!("{} expects a path\n\n{}", flag, USAGE))?;
+            target.push(expand_tilde(&value));
+        }
+
+        if !files.is_empty() || !dirs.is_empty() {
+            return Ok(Self {
+                files,
+                dirs,
+                origin: Origin::CommandLine,
+            });
+        }
+
+        let from_env = |name: &str| -> Vec<PathBuf> {
+            env(name)
+                .map(|value| {
+                    env::split_paths(&value)
+                        .filter(|path| !path.as_os_str().is_empty())
+                        .map(|path| expand_tilde(&path.to_string_lossy()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let files = from_env(CONFIG_FILE_ENV);
+        let dirs = from_env(CONFIG_DIR_ENV);
+        if !files.is_empty() || !dirs.is_empty() {
+            return Ok(Self {
+                files,
+                dirs,
+                origin: Origin::Environment,
+            });
+        }
+
+        if !settings.config_files.is_empty() || !settings.config_dirs.is_empty() {
+            return Ok(Self {
+                files: settings.config_files.clone(),
+                dirs: settings.config_dirs.clone(),
+                origin: Origin::Settings,
+            });
+        }
+
+        Ok(Self::default_locations())
+    }
+
+    /// `~/.ssh/config` and `~/.ssh/config.d`, on Linux and Windows alike.
+    pub fn default_locations() -> Self {
+        let ssh_dir = ssh_dir();
+        Self {
+            files: vec![ssh_dir.join("config")],
+            dirs: vec![ssh_dir.join("config.d")],
+            origin: Origin::Default,
+        }
+    }
+
+    /// Lists the configured files that exist, followed by the files of each
+    /// folder, without duplicates.
+    pub fn discover_files(&self) -> Vec<FileEntry> {
+        let mut entries: Vec<FileEntry> = Vec::new();
+        let singles = self.files.iter().filter(|path| path.is_file()).map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string());
+            FileEntry::new(name, path.clone())
+        });
+        let listed = self
+            .dirs
+            .iter()
+            .filter(|dir| dir.is_dir())
+            .flat_map(get_files_in_folder);
+
+        for entry in singles.chain(listed) {
+            if !entries.iter().any(|known| known.path == entry.path) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+
+    /// Short description for the status bar, with the home folder shown as `~`.
+    pub fn describe(&self) -> String {
+        let home = home_dir();
+        let paths: Vec<String> = self
+            .files
+            .iter()
+            .chain(&self.dirs)
+            .map(
+                |path| match home.as_ref().and_then(|home| path.strip_prefix(home).ok()) {
+                    Some(relative) => format!("~/{}", relative.display()),
+                    None => path.display().to_string(),
+                },
+            )
+            .collect();
+        format!("{} ({})", paths.join(" + "), self.origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let env = |name: &str| match name {
+            CONFIG_DIR_ENV => Some("/env/config.d".to_string()),
+            _ => None,
+        };
+
+        let settings = Settings::default();
+        let cli = ConfigLocations::resolve(
+            args(&["--config-dir", "/cli/dir", "--config-file=/cli/config"]),
+            env,
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(cli.origin, Origin::CommandLine);
+        assert_eq!(cli.dirs, vec![PathBuf::from("/cli/dir")]);
+        assert_eq!(cli.files, vec![PathBuf::from("/cli/config")]);
+
+        let from_env = ConfigLocations::resolve(args(&[]), env, &settings).unwrap();
+        assert_eq!(from_env.origin, Origin::Environment);
+        assert_eq!(from_env.dirs, vec![PathBuf::from("/env/config.d")]);
+        assert!(from_env.files.is_empty());
+
+        let default = ConfigLocations::resolve(args(&[]), |_| None, &settings).unwrap();
+        assert_eq!(default.origin, Origin::Default);
+        assert!(default.files[0].ends_with(".ssh/config"));
+
+        let configured = Settings::parse("[paths]\nconfig_dirs = [\"/team\"]").unwrap();
+        let from_settings = ConfigLocations::resolve(args(&[]), |_| None, &configured).unwrap();
+        assert_eq!(from_settings.origin, Origin::Settings);
+        assert_eq!(from_settings.dirs, vec![PathBuf::from("/team")]);
+
+        assert!(ConfigLocations::resolve(args(&["--config-dir"]), env, &settings).is_err());
+        assert!(ConfigLocations::resolve(args(&["--verbose"]), env, &settings).is_err());
+    }
+
+    #[test]
+    fn test_discover_files() {
+        let temp_dir = env::temp_dir().join("test_locations_discover");
+        fs::create_dir_all(temp_dir.join("config.d")).unwrap();
+        File::create(temp_dir.join("config")).unwrap();
+        File::create(temp_dir.join("config.d").join("work")).unwrap();
+
+        let locations = ConfigLocations {
+            files: vec![temp_dir.join("config"), temp_dir.join("missing")],
+            dirs: vec![temp_dir.join("config.d"), temp_dir.join("config.d")],
+            origin: Origin::CommandLine,
+        };
+        let names: Vec<String> = locations
+            .discover_files()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec!["config", "work"]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
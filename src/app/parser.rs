@@ -1,7 +1,7 @@
-use ratatui::text::Line;
+use super::cst::{ConfigFile, Node};
+use super::paths::{expand_glob, expand_tilde};
 use std::{
-    fs::{self, File},
-    io::{BufRead, BufReader},
+    fs,
     path::{Path, PathBuf},
 };
 
@@ -17,22 +17,107 @@ impl FileEntry {
     }
 }
 
+/// A concrete host alias declared by a `Host` line, together with the options
+/// written in its block and where it was declared.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SshHost {
+    pub alias: String,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<String>,
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    pub local_forwards: Vec<String>,
+    pub remote_forwards: Vec<String>,
+    pub dynamic_forwards: Vec<String>,
+    /// Every other option in the block, in file order, keyword as written.
+    pub options: Vec<(String, String)>,
+    /// Comment lines directly above the `Host` line or inside its block.
+    pub comments: Vec<String>,
+    /// Labels from `# tags: a, b` comments.
+    pub tags: Vec<String>,
+    /// Title of the `# ==== Title ====` section header the host is under.
+    pub group: Option<String>,
+    /// File the `Host` line was read from, which may be an included file.
+    pub source_file: PathBuf,
+    /// 1-based line number of the `Host` line.
+    pub line: usize,
+    /// Top-level config file the host was loaded through, passed to `ssh -F`.
+    pub config_file: PathBuf,
+}
+
+impl SshHost {
+    pub fn new(alias: String, source_file: PathBuf, line: usize) -> Self {
+        Self {
+            alias,
+            config_file: source_file.clone(),
+            source_file,
+            line,
+            ..Default::default()
+        }
+    }
+
+    fn add_comments(&mut self, comments: &[String]) {
+        for comment in comments {
+            // Compared byte for byte, as lowercasing can change the length.
+            let prefix = ["tags:", "tag:"].into_iter().find(|prefix| {
+                comment
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            });
+            if let Some(prefix) = prefix {
+                let list = &comment[prefix.len()..];
+                for tag in list.split([',', ' ']).filter(|tag| !tag.is_empty()) {
+                    if !self.tags.iter().any(|known| known == tag) {
+                        self.tags.push(tag.to_string());
+                    }
+                }
+            }
+            self.comments.push(comment.clone());
+        }
+    }
+
+    /// Records an option from the host's block. Like ssh, the first value
+    /// obtained for a single-valued option wins; forwards and identity files
+    /// accumulate.
+    fn apply_option(&mut self, keyword: &str, value: &str) {
+        let value = value.to_string();
+        match keyword.to_lowercase().as_str() {
+            "hostname" => {
+                self.hostname.get_or_insert(value);
+            }
+            "user" => {
+                self.user.get_or_insert(value);
+            }
+            "port" => {
+                self.port.get_or_insert(value);
+            }
+            "proxyjump" => {
+                self.proxy_jump.get_or_insert(value);
+            }
+            "identityfile" => self.identity_files.push(value),
+            "localforward" => self.local_forwards.push(value),
+            "remoteforward" => self.remote_forwards.push(value),
+            "dynamicforward" => self.dynamic_forwards.push(value),
+            _ => self.options.push((keyword.to_string(), value)),
+        }
+    }
+}
+
 pub fn get_files_in_folder<P: AsRef<Path>>(folder_path: P) -> Vec<FileEntry> {
     let mut files = Vec::new();
     
     match fs::read_dir(folder_path) {
         Ok(entries) => {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(file_name) = path.file_name() {
-                            if let Some(name_str) = file_name.to_str() {
-                                files.push(FileEntry::new(
-                                    name_str.to_string(),
-                                    path.clone()
-                                ));
-                            }
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file() {
+                    if let Some(file_name) = path.file_name() {
+                        if let Some(name_str) = file_name.to_str() {
+                            files.push(FileEntry::new(
+                                name_str.to_string(),
+                                path.clone()
+                            ));
                         }
                     }
                 }
@@ -48,42 +133,278 @@ pub fn get_files_in_folder<P: AsRef<Path>>(folder_path: P) -> Vec<FileEntry> {
     files
 }
 
-pub fn parse_ssh_hosts<P: AsRef<Path>>(filepath: P) -> Vec<Line<'static>> {
-    let file = match File::open(&filepath) {
-        Ok(file) => file,
-        Err(_) => return Vec::new(),
+/// Splits a config line into its keyword and arguments following
+/// ssh_config(5): the keyword is separated from its arguments by whitespace
+/// or by optional whitespace around a single `=`, arguments are separated by
+/// whitespace and may be double quoted, and an unquoted `#` at the start of
+/// an argument begins a comment. Returns `None` for blank lines, comments and
+/// lines with unbalanced quotes.
+pub fn tokenize_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim_start();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let keyword_end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = &line[..keyword_end];
+    if keyword.is_empty() {
+        return None;
+    }
+
+    let mut rest = line[keyword_end..].trim_start();
+    if let Some(after_equals) = rest.strip_prefix('=') {
+        rest = after_equals.trim_start();
+    }
+    Some((keyword.to_string(), split_args(rest)?))
+}
+
+/// Splits the arguments of a config line as [`tokenize_line`] does, stopping
+/// at a comment. Returns `None` when quotes are unbalanced.
+pub fn split_args(rest: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = rest.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some('#') => break,
+            _ => {}
+        }
+
+        let mut arg = String::new();
+        let mut in_quotes = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                '\\' if matches!(chars.peek(), Some('"') | Some('\\')) => {
+                    arg.push(chars.next().unwrap());
+                }
+                c if c.is_whitespace() && !in_quotes => break,
+                c => arg.push(c),
+            }
+        }
+        if in_quotes {
+            return None;
+        }
+        args.push(arg);
+    }
+    Some(args)
+}
+
+/// The title of a section header comment such as `==== Paris DC ====` or
+/// `--- Lab ---`: a run of at least three `=` or `-`, the title, and
+/// optionally the same run again.
+pub fn section_header(comment: &str) -> Option<&str> {
+    let marker = comment.chars().next().filter(|c| matches!(c, '=' | '-'))?;
+    let title = comment.trim_start_matches(marker);
+    if comment.len() - title.len() < 3 {
+        return None;
+    }
+    let title = title.trim_end_matches(marker).trim();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Whether a `Host` argument names a single host rather than a wildcard or
+/// negated pattern.
+pub fn is_concrete_alias(pattern: &str) -> bool {
+    !pattern.is_empty()
+        && !pattern.starts_with('!')
+        && !pattern.contains('*')
+        && !pattern.contains('?')
+}
+
+/// A keyword line read from a config file, after `Include` expansion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    pub keyword: String,
+    pub args: Vec<String>,
+    pub file: PathBuf,
+    /// 1-based line number within `file`.
+    pub line: usize,
+}
+
+impl Directive {
+    pub fn is(&self, keyword: &str) -> bool {
+        self.keyword.eq_ignore_ascii_case(keyword)
+    }
+}
+
+pub enum ConfigEvent<'a> {
+    Directive(&'a Directive),
+    /// A full-line comment, without the `#` and surrounding whitespace.
+    Comment(&'a str),
+    Blank,
+    /// An included file is about to be read. Its `Host` and `Match` lines
+    /// only last until the matching `IncludeEnd`, after which the including
+    /// file's block is in effect again.
+    IncludeStart,
+    IncludeEnd,
+}
+
+/// Same limit as OpenSSH's `READCONF_MAX_DEPTH`.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Reads `path` and every file it includes, calling `visit` for each
+/// directive in the order ssh would see them. `Include` arguments have `~`
+/// expanded, are taken relative to `ssh_dir` when not absolute, and may
+/// contain globs. Returns warnings about unreadable files, include cycles and
+/// excessive nesting; the offending include is skipped.
+pub fn walk_config(
+    path: &Path,
+    ssh_dir: &Path,
+    visit: &mut dyn FnMut(ConfigEvent),
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut stack = Vec::new();
+    read_config_file(path, ssh_dir, &mut stack, &mut warnings, visit);
+    warnings
+}
+
+fn read_config_file(
+    path: &Path,
+    ssh_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    warnings: &mut Vec<String>,
+    visit: &mut dyn FnMut(ConfigEvent),
+) {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        warnings.push(format!("Include cycle: {} is already being read", path.display()));
+        return;
+    }
+    if stack.len() > MAX_INCLUDE_DEPTH {
+        warnings.push(format!(
+            "Include nested too deeply (over {}) at {}",
+            MAX_INCLUDE_DEPTH,
+            path.display()
+        ));
+        return;
+    }
+
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) => {
+            warnings.push(format!("Cannot read {}: {}", path.display(), e));
+            return;
+        }
     };
-    
-    let reader = BufReader::new(file);
-    let mut hosts = Vec::<Line>::new();
-
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            let trimmed = line.trim();
-
-            // Check if line starts with "Host " (case-insensitive)
-            if trimmed.to_lowercase().starts_with("host ") {
-                let host = trimmed
-                    .splitn(2, ' ')
-                    .nth(1)
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-
-                if !host.is_empty() && !host.contains('*') && !host.contains('?') {
-                    let host_line = Line::from(host);
-                    hosts.push(host_line);
+
+    stack.push(canonical);
+    let file = ConfigFile::parse(&String::from_utf8_lossy(&content));
+    for (index, line) in file.lines.iter().enumerate() {
+        let parsed = match &line.node {
+            Node::Blank(_) => {
+                visit(ConfigEvent::Blank);
+                continue;
+            }
+            Node::Comment { .. } => {
+                visit(ConfigEvent::Comment(line.node.comment().unwrap_or_default()));
+                continue;
+            }
+            Node::Directive(parsed) => parsed,
+            Node::Invalid(_) => continue,
+        };
+        let directive = Directive {
+            keyword: parsed.keyword.clone(),
+            args: parsed.args(),
+            file: path.to_path_buf(),
+            line: index + 1,
+        };
+
+        if directive.is("include") {
+            for arg in &directive.args {
+                let mut pattern = expand_tilde(arg);
+                if pattern.is_relative() {
+                    pattern = ssh_dir.join(pattern);
+                }
+                for included in expand_glob(&pattern) {
+                    visit(ConfigEvent::IncludeStart);
+                    read_config_file(&included, ssh_dir, stack, warnings, visit);
+                    visit(ConfigEvent::IncludeEnd);
                 }
             }
+        } else {
+            visit(ConfigEvent::Directive(&directive));
         }
     }
-    hosts
+    stack.pop();
+}
+
+/// Collects the concrete hosts declared in `filepath` and the files it
+/// includes, along with any warnings raised while reading them.
+pub fn parse_ssh_hosts<P: AsRef<Path>>(
+    filepath: P,
+    ssh_dir: &Path,
+) -> (Vec<SshHost>, Vec<String>) {
+    let filepath = filepath.as_ref();
+    let mut hosts = Vec::<SshHost>::new();
+    // Hosts declared by the Host line currently in effect. Options that follow
+    // are applied to all of them until the next Host or Match line.
+    let mut block: Vec<usize> = Vec::new();
+    let mut saved_blocks: Vec<Vec<usize>> = Vec::new();
+    // Comments since the last blank line or directive. They describe the
+    // next Host line, or the current block if an option follows them.
+    let mut comments: Vec<String> = Vec::new();
+    // The section header in effect, restored when an included file ends.
+    let mut group: Option<String> = None;
+    let mut saved_groups: Vec<Option<String>> = Vec::new();
+
+    let warnings = walk_config(filepath, ssh_dir, &mut |event| match event {
+        ConfigEvent::Directive(directive) if directive.is("host") => {
+            block.clear();
+            for alias in directive.args.iter().filter(|arg| is_concrete_alias(arg)) {
+                block.push(hosts.len());
+                let mut host =
+                    SshHost::new(alias.clone(), directive.file.clone(), directive.line);
+                host.config_file = filepath.to_path_buf();
+                host.group = group.clone();
+                hosts.push(host);
+            }
+            for &index in &block {
+                hosts[index].add_comments(&comments);
+            }
+            comments.clear();
+        }
+        ConfigEvent::Directive(directive) if directive.is("match") => {
+            block.clear();
+            comments.clear();
+        }
+        ConfigEvent::Directive(directive) => {
+            let value = directive.args.join(" ");
+            for &index in &block {
+                hosts[index].add_comments(&comments);
+                hosts[index].apply_option(&directive.keyword, &value);
+            }
+            comments.clear();
+        }
+        ConfigEvent::Comment(text) => match section_header(text) {
+            Some(title) => {
+                group = Some(title.to_string());
+                comments.clear();
+            }
+            None => comments.push(text.to_string()),
+        },
+        ConfigEvent::Blank => comments.clear(),
+        ConfigEvent::IncludeStart => {
+            saved_blocks.push(block.clone());
+            saved_groups.push(group.clone());
+            comments.clear();
+        }
+        ConfigEvent::IncludeEnd => {
+            block = saved_blocks.pop().unwrap_or_default();
+            group = saved_groups.pop().unwrap_or_default();
+            comments.clear();
+        }
+    });
+
+    (hosts, warnings)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use std::fs::{self, File};
     use std::io::Write;
 
     #[test]
@@ -119,10 +440,172 @@ mod tests {
         writeln!(file, "Host server2").unwrap();
         writeln!(file, "    HostName 192.168.1.2").unwrap();
 
-        let hosts = parse_ssh_hosts(&config_path);
+        let (hosts, _) = parse_ssh_hosts(&config_path, &temp_dir);
         assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].alias, "server1");
+        assert_eq!(hosts[0].hostname.as_deref(), Some("192.168.1.1"));
+        assert_eq!(hosts[1].line, 3);
         
         // Cleanup
         fs::remove_dir_all(&temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_tokenize_line() {
+        let tokens = |line: &str| tokenize_line(line).map(|(k, a)| (k, a.join("|")));
+
+        assert_eq!(tokens("Host web"), Some(("Host".into(), "web".into())));
+        assert_eq!(tokens("\tHost\tfoo  bar"), Some(("Host".into(), "foo|bar".into())));
+        assert_eq!(tokens("Host=foo"), Some(("Host".into(), "foo".into())));
+        assert_eq!(tokens("User = admin"), Some(("User".into(), "admin".into())));
+        assert_eq!(
+            tokens(r#"IdentityFile "C:/My Keys/id_rsa" other"#),
+            Some(("IdentityFile".into(), "C:/My Keys/id_rsa|other".into()))
+        );
+        assert_eq!(tokens("Port 22 # ssh"), Some(("Port".into(), "22".into())));
+        assert_eq!(tokens("   # comment"), None);
+        assert_eq!(tokens(""), None);
+        assert_eq!(tokens(r#"User "unterminated"#), None);
+    }
+
+    #[test]
+    fn test_parse_multiple_aliases() {
+        let temp_dir = std::env::temp_dir().join("test_ssh_parse_aliases");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let config_path = temp_dir.join("test_config");
+        let mut file = File::create(&config_path).unwrap();
+        writeln!(file, "Host web1 web2 *.prod !bastion web3").unwrap();
+        writeln!(file, "    user deploy").unwrap();
+        writeln!(file, "HOST\tdb").unwrap();
+        writeln!(file, "Host=cache").unwrap();
+        writeln!(file, "Host *").unwrap();
+        writeln!(file, "    User nobody").unwrap();
+
+        let (hosts, _) = parse_ssh_hosts(&config_path, &temp_dir);
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["web1", "web2", "web3", "db", "cache"]);
+        assert!(hosts[..3].iter().all(|h| h.user.as_deref() == Some("deploy")));
+        assert_eq!(hosts[3].user, None);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_ssh_hosts_follows_includes() {
+        let temp_dir = std::env::temp_dir().join("test_ssh_parse_include");
+        fs::create_dir_all(temp_dir.join("config.d")).unwrap();
+
+        let config_path = temp_dir.join("config");
+        let mut file = File::create(&config_path).unwrap();
+        writeln!(file, "Host top").unwrap();
+        writeln!(file, "    Include config.d/*").unwrap();
+        writeln!(file, "    Port 2200").unwrap();
+        writeln!(file, "Include {}", temp_dir.join("config").display()).unwrap();
+
+        let mut file = File::create(temp_dir.join("config.d").join("a")).unwrap();
+        writeln!(file, "    User fromtop").unwrap();
+        writeln!(file, "Host included").unwrap();
+        writeln!(file, "    HostName 10.0.0.5").unwrap();
+        writeln!(file, "Include missing-*").unwrap();
+
+        let (hosts, warnings) = parse_ssh_hosts(&config_path, &temp_dir);
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["top", "included"]);
+
+        // Options before the included file's first Host line belong to the
+        // including block, and the block resumes after the include.
+        assert_eq!(hosts[0].user.as_deref(), Some("fromtop"));
+        assert_eq!(hosts[0].port.as_deref(), Some("2200"));
+        assert_eq!(hosts[1].port, None);
+
+        assert_eq!(hosts[1].source_file, temp_dir.join("config.d").join("a"));
+        assert_eq!(hosts[1].line, 2);
+        assert_eq!(hosts[1].config_file, config_path);
+
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("cycle"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_comments_and_tags() {
+        let temp_dir = std::env::temp_dir().join("test_ssh_parse_comments");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let config_path = temp_dir.join("test_config");
+        let mut file = File::create(&config_path).unwrap();
+        writeln!(file, "# unrelated header").unwrap();
+        writeln!(file).unwrap();
+        writeln!(file, "Host bastion").unwrap();
+        writeln!(file, "# ==== Paris DC ====").unwrap();
+        writeln!(file, "# Main database").unwrap();
+        writeln!(file, "# tags: prod, db").unwrap();
+        writeln!(file, "Host db").unwrap();
+        writeln!(file, "    # Tags: Paris").unwrap();
+        writeln!(file, "    HostName 10.1.2.3").unwrap();
+        writeln!(file, "#--- Lab").unwrap();
+        writeln!(file, "Host web").unwrap();
+
+        let (hosts, _) = parse_ssh_hosts(&config_path, &temp_dir);
+        assert_eq!(hosts[1].comments, vec!["Main database", "tags: prod, db", "Tags: Paris"]);
+        assert_eq!(hosts[1].tags, vec!["prod", "db", "Paris"]);
+        assert!(hosts[2].comments.is_empty());
+        let groups: Vec<Option<&str>> = hosts.iter().map(|host| host.group.as_deref()).collect();
+        assert_eq!(groups, vec![None, Some("Paris DC"), Some("Lab")]);
+        assert_eq!(section_header("== not a header"), None);
+        assert_eq!(section_header("======"), None);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_non_ascii_tags() {
+        let mut host = SshHost::new("web".to_string(), "config".into(), 1);
+        host.add_comments(&[
+            "tags: İİİİİİ".to_string(),
+            "Tag: \u{212A}x, été".to_string(),
+            "\u{212A}ags: not a tag".to_string(),
+        ]);
+        assert_eq!(host.tags, vec!["İİİİİİ", "\u{212A}x", "été"]);
+    }
+
+    #[test]
+    fn test_parse_ssh_host_options() {
+        let temp_dir = std::env::temp_dir().join("test_ssh_parse_options");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let config_path = temp_dir.join("test_config");
+        let mut file = File::create(&config_path).unwrap();
+        writeln!(file, "# bastion").unwrap();
+        writeln!(file, "Host jump").unwrap();
+        writeln!(file, "    User admin").unwrap();
+        writeln!(file, "    Port 2222").unwrap();
+        writeln!(file, "    User ignored").unwrap();
+        writeln!(file, "    IdentityFile ~/.ssh/id_a").unwrap();
+        writeln!(file, "    IdentityFile ~/.ssh/id_b").unwrap();
+        writeln!(file, "    ProxyJump gw").unwrap();
+        writeln!(file, "    LocalForward 8080 localhost:80").unwrap();
+        writeln!(file, "    ServerAliveInterval 30").unwrap();
+        writeln!(file, "Match user root").unwrap();
+        writeln!(file, "    Port 22").unwrap();
+
+        let (hosts, _) = parse_ssh_hosts(&config_path, &temp_dir);
+        assert_eq!(hosts.len(), 1);
+        let host = &hosts[0];
+        assert_eq!(host.user.as_deref(), Some("admin"));
+        assert_eq!(host.port.as_deref(), Some("2222"));
+        assert_eq!(host.identity_files, vec!["~/.ssh/id_a", "~/.ssh/id_b"]);
+        assert_eq!(host.proxy_jump.as_deref(), Some("gw"));
+        assert_eq!(host.local_forwards, vec!["8080 localhost:80"]);
+        assert_eq!(
+            host.options,
+            vec![("ServerAliveInterval".to_string(), "30".to_string())]
+        );
+        assert_eq!(host.source_file, config_path);
+        assert_eq!(host.line, 2);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
 }
@@ -0,0 +1,102 @@
+use super::pattern::{has_wildcard, wildcard_match};
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// The per-user OpenSSH directory, `~/.ssh`.
+pub fn ssh_dir() -> PathBuf {
+    home_dir().unwrap_or_default().join(".ssh")
+}
+
+/// Replaces a leading `~` with the user's home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let home = home_dir().unwrap_or_default();
+            home.join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Expands `*` and `?` wildcards in any component of `path`, returning the
+/// existing paths that match in sorted order. A path without wildcards is
+/// returned as is when it exists. Like the shell, wildcards do not match
+/// names starting with a dot unless the pattern does.
+pub fn expand_glob(path: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in path.components() {
+        let part = component.as_os_str().to_string_lossy();
+        let is_pattern = matches!(component, Component::Normal(_)) && has_wildcard(&part);
+
+        if !is_pattern {
+            for candidate in &mut candidates {
+                candidate.push(component);
+            }
+            continue;
+        }
+
+        let mut next = Vec::new();
+        for candidate in &candidates {
+            let dir = if candidate.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                candidate.as_path()
+            };
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut matches: Vec<PathBuf> = entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let hidden = name.starts_with('.') && !part.starts_with('.');
+                    (!hidden && wildcard_match(&part, &name)).then(|| candidate.join(name))
+                })
+                .collect();
+            matches.sort();
+            next.extend(matches);
+        }
+        candidates = next;
+    }
+
+    candidates.retain(|candidate| candidate.exists());
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_expand_glob() {
+        let temp_dir = env::temp_dir().join("test_paths_glob");
+        fs::create_dir_all(temp_dir.join("conf.d")).unwrap();
+        for name in ["b.conf", "a.conf", ".hidden.conf", "notes.txt"] {
+            File::create(temp_dir.join("conf.d").join(name)).unwrap();
+        }
+
+        let found = expand_glob(&temp_dir.join("conf.d").join("*.conf"));
+        assert_eq!(
+            found,
+            vec![
+                temp_dir.join("conf.d").join("a.conf"),
+                temp_dir.join("conf.d").join("b.conf"),
+            ]
+        );
+        assert_eq!(expand_glob(&temp_dir.join("c*").join("n*")).len(), 1);
+        assert!(expand_glob(&temp_dir.join("missing")).is_empty());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
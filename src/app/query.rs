@@ -0,0 +1,270 @@
+use super::fuzzy::{self, FieldMatch, HostField, MatchKind};
+use super::parser::SshHost;
+
+/// One search term, e.g. `'prod`, `^web`, `!staging` or `user:root`.
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    text: String,
+    kind: MatchKind,
+    /// Host field the term is restricted to, for `user:` style filters.
+    field: Option<HostField>,
+    negated: bool,
+}
+
+/// Field filter prefixes understood in queries.
+fn filter_field(name: &str) -> Option<HostField> {
+    match name.to_ascii_lowercase().as_str() {
+        "user" => Some(HostField::User),
+        "host" | "hostname" => Some(HostField::HostName),
+        "port" => Some(HostField::Port),
+        "file" => Some(HostField::File),
+        "tag" => Some(HostField::Tag),
+        _ => None,
+    }
+}
+
+impl Term {
+    /// Parses a single whitespace-free token. Returns `None` when nothing is
+    /// left to match once the operators are stripped, as while typing `!`.
+    fn parse(token: &str) -> Option<Self> {
+        let (negated, rest) = match token.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+        let (field, rest) = match rest
+            .split_once(':')
+            .and_then(|(name, value)| Some((filter_field(name)?, value)))
+        {
+            Some((field, value)) => (Some(field), value),
+            None => (None, rest),
+        };
+        let (exact, rest) = match rest.strip_prefix('\'') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (prefix, rest) = match rest.strip_prefix('^') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (suffix, rest) = match rest.strip_suffix('$') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, rest),
+        };
+        if rest.is_empty() {
+            return None;
+        }
+
+        // Negations and field filters match substrings unless anchored, and
+        // a port has to match in full.
+        let kind = match (prefix, suffix) {
+            (true, true) => MatchKind::Equal,
+            (true, false) => MatchKind::Prefix,
+            (false, true) => MatchKind::Suffix,
+            _ if field == Some(HostField::Port) => MatchKind::Equal,
+            _ if exact || negated || field.is_some() => MatchKind::Exact,
+            _ => MatchKind::Fuzzy,
+        };
+        Some(Term {
+            text: rest.to_string(),
+            kind,
+            field,
+            negated,
+        })
+    }
+
+    /// `None` when the term rejects the host. Otherwise what it matched, if
+    /// anything: negated terms accept a host without matching any part of it.
+    fn evaluate(&self, host: &SshHost) -> Option<Option<FieldMatch>> {
+        let found = match self.field {
+            None => fuzzy::match_host(host, &self.text, self.kind),
+            Some(field) => field_values(host, field)
+                .into_iter()
+                .filter_map(|value| {
+                    let (score, positions) = fuzzy::match_text(&value, &self.text, self.kind)?;
+                    Some(FieldMatch {
+                        field,
+                        text: value,
+                        positions,
+                        score,
+                    })
+                })
+                .max_by_key(|found| found.score),
+        };
+        match (found, self.negated) {
+            (Some(found), false) => Some(Some(found)),
+            (None, true) => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// The values a field filter is matched against.
+fn field_values(host: &SshHost, field: HostField) -> Vec<String> {
+    let file_name = |path: &std::path::Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    match field {
+        HostField::User => host.user.iter().cloned().collect(),
+        HostField::HostName => host.hostname.iter().cloned().collect(),
+        HostField::Port => vec![host.port.clone().unwrap_or_else(|| "22".to_string())],
+        HostField::Tag => host.tags.clone(),
+        HostField::File => {
+            let mut names: Vec<String> = file_name(&host.source_file).into_iter().collect();
+            names.extend(file_name(&host.config_file).filter(|name| !names.contains(name)));
+            names
+        }
+        HostField::Alias => vec![host.alias.clone()],
+        HostField::ProxyJump => host.proxy_jump.iter().cloned().collect(),
+        HostField::Comment => host.comments.clone(),
+    }
+}
+
+/// A parsed search query in fzf's extended syntax: whitespace separated terms
+/// that must all match, where terms joined by a lone `|` are alternatives.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    groups: Vec<Vec<Term>>,
+}
+
+impl Query {
+    /// Parses the search box text. A term is fuzzy unless written as
+    /// `'exact`, `^prefix`, `suffix$` or `^equal$`; `!term` excludes hosts
+    /// containing it. `user:`, `host:`, `port:`, `file:` and `tag:` restrict a
+    /// term to one field.
+    pub fn parse(input: &str) -> Self {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut alternative = false;
+        for token in input.split_whitespace() {
+            if token == "|" {
+                alternative = !groups.is_empty();
+                continue;
+            }
+            let Some(term) = Term::parse(token) else {
+                continue;
+            };
+            match groups.last_mut() {
+                Some(group) if alternative => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            alternative = false;
+        }
+        Query { groups }
+    }
+
+    /// Matches a host against every term. The result describes the best
+    /// scoring field, with the positions other terms matched in that same
+    /// field added, and the score of all terms summed.
+    pub fn matches(&self, host: &SshHost) -> Option<FieldMatch> {
+        let mut found = Vec::new();
+        for group in &self.groups {
+            let best = group
+                .iter()
+                .filter_map(|term| term.evaluate(host))
+                .max_by_key(|found| found.as_ref().map(|found| found.score))?;
+            found.extend(best);
+        }
+
+        let total = found.iter().map(|found| found.score).sum();
+        let Some(best) = found.iter().max_by_key(|found| found.score) else {
+            return Some(FieldMatch {
+                field: HostField::Alias,
+                text: host.alias.clone(),
+                positions: Vec::new(),
+                score: 0,
+            });
+        };
+        let mut positions: Vec<usize> = found
+            .iter()
+            .filter(|other| other.field == best.field && other.text == best.text)
+            .flat_map(|other| other.positions.iter().copied())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        Some(FieldMatch {
+            field: best.field,
+            text: best.text.clone(),
+            positions,
+            score: total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let term = |text: &str, kind, field, negated| Term {
+            text: text.to_string(),
+            kind,
+            field,
+            negated,
+        };
+        assert_eq!(
+            Query::parse("'exact ^pre suf$ !neg user:root a | b | c ! ^").groups,
+            vec![
+                vec![term("exact", MatchKind::Exact, None, false)],
+                vec![term("pre", MatchKind::Prefix, None, false)],
+                vec![term("suf", MatchKind::Suffix, None, false)],
+                vec![term("neg", MatchKind::Exact, None, true)],
+                vec![term("root", MatchKind::Exact, Some(HostField::User), false)],
+                vec![
+                    term("a", MatchKind::Fuzzy, None, false),
+                    term("b", MatchKind::Fuzzy, None, false),
+                    term("c", MatchKind::Fuzzy, None, false),
+                ],
+            ]
+        );
+
+        let port = &Query::parse("!port:2222").groups[0][0];
+        assert_eq!((port.kind, port.negated), (MatchKind::Equal, true));
+        // Colons in addresses are not field filters.
+        assert_eq!(Query::parse("fe80::1").groups[0][0].field, None);
+        assert_eq!(Query::parse("   "), Query::default());
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let mut web = SshHost::new("web-prod".to_string(), "config.d/paris".into(), 1);
+        web.hostname = Some("10.0.0.5".to_string());
+        web.user = Some("root".to_string());
+        web.tags = vec!["prod".to_string(), "frontend".to_string()];
+        let mut db = SshHost::new("db-staging".to_string(), "config.d/lyon".into(), 4);
+        db.user = Some("postgres".to_string());
+        db.port = Some("2222".to_string());
+        db.tags = vec!["db".to_string()];
+        let hosts = [web, db];
+
+        let aliases = |query: &str| -> Vec<&str> {
+            let query = Query::parse(query);
+            hosts
+                .iter()
+                .filter(|host| query.matches(host).is_some())
+                .map(|host| host.alias.as_str())
+                .collect()
+        };
+        assert_eq!(aliases("user:root"), vec!["web-prod"]);
+        assert_eq!(aliases("port:22"), vec!["web-prod"]);
+        assert_eq!(aliases("port:2222"), vec!["db-staging"]);
+        assert_eq!(aliases("file:lyon"), vec!["db-staging"]);
+        assert_eq!(aliases("tag:db"), vec!["db-staging"]);
+        assert_eq!(aliases("!prod"), vec!["db-staging"]);
+        assert_eq!(aliases("^db"), vec!["db-staging"]);
+        assert_eq!(aliases("prod$"), vec!["web-prod"]);
+        assert_eq!(aliases("'eb-p"), vec!["web-prod"]);
+        assert_eq!(aliases("'wbp"), Vec::<&str>::new());
+        assert_eq!(
+            aliases("tag:db | user:root"),
+            vec!["web-prod", "db-staging"]
+        );
+        assert_eq!(aliases("wp | dbs !tag:db"), vec!["web-prod"]);
+        assert_eq!(aliases(""), vec!["web-prod", "db-staging"]);
+
+        // Terms matching the same field are highlighted together.
+        let found = Query::parse("web prod").matches(&hosts[0]).unwrap();
+        assert_eq!(found.field, HostField::Alias);
+        assert_eq!(found.positions, vec![0, 1, 2, 4, 5, 6, 7]);
+    }
+}
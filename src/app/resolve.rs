@@ -0,0 +1,372 @@
+//! Computes the configuration ssh would use for a host, like `ssh -G`, by
+//! applying every matching `Host` and `Match` block in file order.
+
+use super::parser::{walk_config, ConfigEvent, Directive};
+use super::pattern::wildcard_match;
+use std::{env, path::Path};
+
+/// Options that ssh accumulates across blocks instead of keeping the first
+/// value obtained.
+const MULTI_VALUED: &[&str] = &[
+    "certificatefile",
+    "dynamicforward",
+    "identityfile",
+    "localforward",
+    "remoteforward",
+    "sendenv",
+    "setenv",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Options before the first `Host` or `Match` line apply to every host.
+    Always,
+    Host(Vec<String>),
+    Match(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub condition: Condition,
+    /// The `Host` or `Match` line itself, absent for `Condition::Always`.
+    pub header: Option<Directive>,
+    pub directives: Vec<Directive>,
+}
+
+impl Block {
+    fn new(condition: Condition, header: Option<Directive>) -> Self {
+        Self {
+            condition,
+            header,
+            directives: Vec::new(),
+        }
+    }
+}
+
+/// Splits `path` and its includes into blocks. When an included file ends,
+/// the including file's block condition applies again, so it is reopened as a
+/// new block.
+pub fn read_blocks(path: &Path, ssh_dir: &Path) -> (Vec<Block>, Vec<String>) {
+    let mut blocks = vec![Block::new(Condition::Always, None)];
+    let mut saved: Vec<Block> = Vec::new();
+
+    let warnings = walk_config(path, ssh_dir, &mut |event| match event {
+        ConfigEvent::Directive(directive) if directive.is("host") => {
+            let condition = Condition::Host(directive.args.clone());
+            blocks.push(Block::new(condition, Some(directive.clone())));
+        }
+        ConfigEvent::Directive(directive) if directive.is("match") => {
+            let condition = Condition::Match(directive.args.clone());
+            blocks.push(Block::new(condition, Some(directive.clone())));
+        }
+        ConfigEvent::Directive(directive) => {
+            if let Some(block) = blocks.last_mut() {
+                block.directives.push(directive.clone());
+            }
+        }
+        ConfigEvent::IncludeStart => {
+            if let Some(block) = blocks.last() {
+                saved.push(Block::new(block.condition.clone(), block.header.clone()));
+            }
+        }
+        ConfigEvent::IncludeEnd => {
+            if let Some(block) = saved.pop() {
+                blocks.push(block);
+            }
+        }
+        ConfigEvent::Comment(_) | ConfigEvent::Blank => {}
+    });
+
+    blocks.retain(|block| block.header.is_some() || !block.directives.is_empty());
+    (blocks, warnings)
+}
+
+/// One option of the effective configuration and where its value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveOption {
+    /// Keyword as first written in the config.
+    pub keyword: String,
+    pub value: String,
+    /// `None` for values ssh falls back to when nothing sets them.
+    pub origin: Option<(std::path::PathBuf, usize)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EffectiveConfig {
+    pub options: Vec<EffectiveOption>,
+    /// Conditions that could not be evaluated, such as `Match exec`.
+    pub notes: Vec<String>,
+}
+
+impl EffectiveConfig {
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|option| option.keyword.eq_ignore_ascii_case(keyword))
+            .map(|option| option.value.as_str())
+    }
+
+    pub fn get_all(&self, keyword: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|option| option.keyword.eq_ignore_ascii_case(keyword))
+            .map(|option| option.value.as_str())
+            .collect()
+    }
+
+    fn obtain(&mut self, directive: &Directive) {
+        let value = directive.args.join(" ");
+        let multi = MULTI_VALUED.contains(&directive.keyword.to_lowercase().as_str());
+        if multi || self.get(&directive.keyword).is_none() {
+            self.options.push(EffectiveOption {
+                keyword: directive.keyword.clone(),
+                value,
+                origin: Some((directive.file.clone(), directive.line)),
+            });
+        }
+    }
+
+    fn set_default(&mut self, keyword: &str, value: String) {
+        if self.get(keyword).is_none() {
+            self.options.push(EffectiveOption {
+                keyword: keyword.to_string(),
+                value,
+                origin: None,
+            });
+        }
+    }
+
+    /// The host ssh will actually connect to, with `%h` expanded.
+    fn hostname(&self, alias: &str) -> String {
+        self.get("hostname")
+            .map(|hostname| hostname.replace("%h", alias))
+            .unwrap_or_else(|| alias.to_string())
+    }
+}
+
+pub fn local_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// Evaluates `blocks` for `alias` with first-obtained-value semantics. When
+/// any block uses `Match canonical` or `Match final`, a second pass is made in
+/// which those criteria match, as ssh does after hostname canonicalisation.
+pub fn evaluate(blocks: &[Block], alias: &str, local_user: &str) -> EffectiveConfig {
+    let mut config = EffectiveConfig::default();
+    let needs_final_pass = blocks.iter().any(|block| match &block.condition {
+        Condition::Match(criteria) => criteria.iter().any(|criterion| {
+            let criterion = criterion.trim_start_matches('!');
+            criterion.eq_ignore_ascii_case("canonical") || criterion.eq_ignore_ascii_case("final")
+        }),
+        _ => false,
+    });
+
+    for final_pass in [false, true] {
+        if final_pass && !needs_final_pass {
+            break;
+        }
+        for block in blocks {
+            let applies = match &block.condition {
+                Condition::Always => true,
+                Condition::Host(patterns) => {
+                    match_pattern_list(patterns.iter().map(String::as_str), alias)
+                }
+                Condition::Match(criteria) => {
+                    match_criteria(criteria, block, alias, local_user, final_pass, &mut config)
+                }
+            };
+            if applies {
+                for directive in &block.directives {
+                    config.obtain(directive);
+                }
+            }
+        }
+    }
+
+    let hostname = config.hostname(alias);
+    if let Some(option) = config
+        .options
+        .iter_mut()
+        .find(|option| option.keyword.eq_ignore_ascii_case("hostname"))
+    {
+        option.value = hostname;
+    } else {
+        config.set_default("HostName", hostname);
+    }
+    config.set_default("User", local_user.to_string());
+    config.set_default("Port", "22".to_string());
+    config
+}
+
+/// ssh's `match_pattern_list`: a matching negated pattern rejects outright,
+/// otherwise any matching positive pattern accepts. Patterns are compared
+/// case-insensitively, as host names are.
+fn match_pattern_list<'a>(patterns: impl Iterator<Item = &'a str>, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut matched = false;
+    for pattern in patterns {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if wildcard_match(&pattern.to_lowercase(), &text) {
+            if negated {
+                return false;
+            }
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn match_criteria(
+    criteria: &[String],
+    block: &Block,
+    alias: &str,
+    local_user: &str,
+    final_pass: bool,
+    config: &mut EffectiveConfig,
+) -> bool {
+    let location = block
+        .header
+        .as_ref()
+        .map(|header| format!("{}:{}", header.file.display(), header.line))
+        .unwrap_or_default();
+
+    let mut args = criteria.iter();
+    let mut result = true;
+    while let Some(criterion) = args.next() {
+        let (negated, name) = match criterion.strip_prefix('!') {
+            Some(name) => (true, name.to_lowercase()),
+            None => (false, criterion.to_lowercase()),
+        };
+
+        let matched = match name.as_str() {
+            "all" => true,
+            "canonical" | "final" => final_pass,
+            _ => {
+                let Some(arg) = args.next() else {
+                    config
+                        .notes
+                        .push(format!("Match {} without argument at {}", name, location));
+                    return false;
+                };
+                let patterns = || arg.split(',');
+                match name.as_str() {
+                    "host" => match_pattern_list(patterns(), &config.hostname(alias)),
+                    "originalhost" => match_pattern_list(patterns(), alias),
+                    "user" => {
+                        let user = config.get("user").unwrap_or(local_user).to_string();
+                        match_pattern_list(patterns(), &user)
+                    }
+                    "localuser" => match_pattern_list(patterns(), local_user),
+                    "tagged" => match_pattern_list(patterns(), config.get("tag").unwrap_or("")),
+                    _ => {
+                        let note = format!("Match {} not evaluated at {}", name, location);
+                        if !config.notes.contains(&note) {
+                            config.notes.push(note);
+                        }
+                        false
+                    }
+                }
+            }
+        };
+
+        if matched == negated {
+            result = false;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    fn blocks_for(name: &str, lines: &[&str]) -> Vec<Block> {
+        let temp_dir = env::temp_dir().join(name);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let config_path = temp_dir.join("config");
+        let mut file = File::create(&config_path).unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        let (blocks, _) = read_blocks(&config_path, &temp_dir);
+        fs::remove_dir_all(&temp_dir).unwrap();
+        blocks
+    }
+
+    #[test]
+    fn test_evaluate_first_value_wins() {
+        let blocks = blocks_for(
+            "test_resolve_first_value",
+            &[
+                "Compression yes",
+                "Host db1",
+                "    HostName %h.example.com",
+                "    IdentityFile ~/.ssh/db",
+                "Host *.prod db*",
+                "    User deploy",
+                "    IdentityFile ~/.ssh/prod",
+                "Host * !db1",
+                "    User nobody",
+                "Host *",
+                "    Port 2222",
+                "    User fallback",
+            ],
+        );
+
+        let config = evaluate(&blocks, "db1", "me");
+        assert_eq!(config.get("hostname"), Some("db1.example.com"));
+        assert_eq!(config.get("user"), Some("deploy"));
+        assert_eq!(config.get("port"), Some("2222"));
+        assert_eq!(config.get("compression"), Some("yes"));
+        assert_eq!(
+            config.get_all("identityfile"),
+            vec!["~/.ssh/db", "~/.ssh/prod"]
+        );
+
+        let config = evaluate(&blocks, "web", "me");
+        assert_eq!(config.get("hostname"), Some("web"));
+        assert_eq!(config.get("user"), Some("nobody"));
+        let origin = config.options.iter().find(|o| o.keyword == "User").unwrap();
+        assert_eq!(origin.origin.as_ref().unwrap().1, 9);
+    }
+
+    #[test]
+    fn test_evaluate_match_blocks() {
+        let blocks = blocks_for(
+            "test_resolve_match",
+            &[
+                "Host web",
+                "    HostName web.internal",
+                "Match host *.internal user root",
+                "    Port 2200",
+                "Match originalhost web !localuser me",
+                "    IdentityFile ~/.ssh/other",
+                "Match exec \"true\"",
+                "    User exec",
+                "Match final host *.internal",
+                "    User late",
+                "Match all",
+                "    ProxyJump bastion",
+            ],
+        );
+
+        let config = evaluate(&blocks, "web", "me");
+        assert_eq!(config.get("port"), Some("22"));
+        assert!(config.get("identityfile").is_none());
+        assert_eq!(config.get("user"), Some("late"));
+        assert_eq!(config.get("proxyjump"), Some("bastion"));
+        assert_eq!(config.notes.len(), 1);
+
+        let config = evaluate(&blocks, "web", "root");
+        assert_eq!(config.get("identityfile"), Some("~/.ssh/other"));
+        assert_eq!(config.get("port"), Some("2200"));
+        assert_eq!(evaluate(&blocks, "other", "root").get("user"), Some("root"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Production hosts, and the typed confirmation asked before connecting to
+//! one.
+
+use super::launcher::LaunchTarget;
+use super::parser::SshHost;
+use super::pattern::wildcard_match;
+use super::settings::ProductionSettings;
+
+/// How a production host is guarded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protection {
+    /// Shown as production.
+    Marked,
+    /// Shown as production, and its alias has to be typed before connecting.
+    Confirm,
+}
+
+impl Protection {
+    fn from_confirm(confirm: bool) -> Self {
+        if confirm {
+            Protection::Confirm
+        } else {
+            Protection::Marked
+        }
+    }
+}
+
+/// Whether `host` is production: the first pattern from the settings
+/// matching its alias or HostName decides, otherwise a production tag.
+pub fn protection(settings: &ProductionSettings, host: &SshHost) -> Option<Protection> {
+    let names = [Some(host.alias.as_str()), host.hostname.as_deref()];
+    let rule = settings.hosts.iter().find(|(pattern, _)| {
+        names
+            .iter()
+            .flatten()
+            .any(|name| wildcard_match(pattern, name))
+    });
+    if let Some((_, confirm)) = rule {
+        return Some(Protection::from_confirm(*confirm));
+    }
+
+    let tagged = host.tags.iter().any(|tag| {
+        settings
+            .tags
+            .iter()
+            .any(|production| production.eq_ignore_ascii_case(tag))
+    });
+    tagged.then(|| Protection::from_confirm(settings.confirm))
+}
+
+/// A connection to a production host waiting for its alias to be typed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Confirmation {
+    pub target: LaunchTarget,
+    pub input: String,
+}
+
+impl Confirmation {
+    pub fn new(target: LaunchTarget) -> Self {
+        Self {
+            target,
+            input: String::new(),
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.input == self.target.alias
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protection() {
+        let settings = ProductionSettings {
+            hosts: vec![
+                ("*.staging".to_string(), false),
+                ("*.prod".to_string(), true),
+            ],
+            ..ProductionSettings::default()
+        };
+        let host = |alias: &str, hostname: Option<&str>, tags: &[&str]| {
+            let mut host = SshHost::new(alias.to_string(), "config".into(), 1);
+            host.hostname = hostname.map(str::to_string);
+            host.tags = tags.iter().map(|tag| tag.to_string()).collect();
+            host
+        };
+
+        let check = |host: &SshHost| protection(&settings, host);
+        assert_eq!(
+            check(&host("db.prod", None, &[])),
+            Some(Protection::Confirm)
+        );
+        assert_eq!(
+            check(&host("db", Some("db.prod"), &[])),
+            Some(Protection::Confirm)
+        );
+        assert_eq!(
+            check(&host("web", None, &["Prod"])),
+            Some(Protection::Confirm)
+        );
+        // A pattern rule wins over the tag.
+        assert_eq!(
+            check(&host("web.staging", None, &["prod"])),
+            Some(Protection::Marked)
+        );
+        assert_eq!(check(&host("web", None, &["dev"])), None);
+
+        let unconfirmed = ProductionSettings {
+            confirm: false,
+            ..ProductionSettings::default()
+        };
+        assert_eq!(
+            protection(&unconfirmed, &host("web", None, &["production"])),
+            Some(Protection::Marked)
+        );
+    }
+}
@@ -0,0 +1,668 @@
+//! User settings read from `settings.toml` in the platform config folder.
+
+use super::launcher::{LauncherKind, TmuxMode};
+use super::paths::{expand_tilde, home_dir};
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    style::Color,
+};
+use serde::Deserialize;
+use std::{env, fmt, fs, path::PathBuf, str::FromStr};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+/// `$XDG_CONFIG_HOME/sshtui`, falling back to `~/.config/sshtui`, or
+/// `%APPDATA%\sshtui` on Windows.
+pub fn settings_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))
+    };
+    base.map(|base| base.join("sshtui"))
+}
+
+#[derive(Debug)]
+pub struct SettingsError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid settings in {}: {}",
+            self.path.display(),
+            self.message
+        )
+    }
+}
+
+/// The settings file as written, before validation.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsFile {
+    paths: PathsFile,
+    launcher: LauncherFile,
+    keys: KeysFile,
+    theme: ThemeFile,
+    production: ProductionFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PathsFile {
+    config_files: Vec<String>,
+    config_dirs: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LauncherFile {
+    kind: Option<String>,
+    command: Option<Vec<String>>,
+    tmux: Option<String>,
+    hosts: Vec<HostLauncherFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostLauncherFile {
+    pattern: String,
+    kind: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KeysFile {
+    quit: Option<Vec<String>>,
+    search: Option<Vec<String>>,
+    global_search: Option<Vec<String>>,
+    history: Option<Vec<String>>,
+    favourite: Option<Vec<String>>,
+    tag_filter: Option<Vec<String>>,
+    add_host: Option<Vec<String>>,
+    edit_host: Option<Vec<String>>,
+    delete_host: Option<Vec<String>>,
+    move_host: Option<Vec<String>>,
+    copy_host: Option<Vec<String>>,
+    rename_host: Option<Vec<String>>,
+    mark: Option<Vec<String>>,
+    bulk_edit: Option<Vec<String>>,
+    undo: Option<Vec<String>>,
+    redo: Option<Vec<String>>,
+    backups: Option<Vec<String>>,
+    connect: Option<Vec<String>>,
+    back: Option<Vec<String>>,
+    up: Option<Vec<String>>,
+    down: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProductionFile {
+    tags: Option<Vec<String>>,
+    confirm: Option<bool>,
+    colour: Option<String>,
+    badge: Option<String>,
+    hosts: Vec<ProductionHostFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProductionHostFile {
+    pattern: String,
+    confirm: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    highlight: Option<String>,
+    accent: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// Config files and folders to use when neither the command line nor the
+    /// environment names any.
+    pub config_files: Vec<PathBuf>,
+    pub config_dirs: Vec<PathBuf>,
+    pub launcher: LauncherSettings,
+    pub keys: KeyMap,
+    pub theme: Theme,
+    pub production: ProductionSettings,
+}
+
+impl Settings {
+    /// Reads the settings file from [`settings_dir`], using the defaults when
+    /// it does not exist.
+    pub fn load() -> Result<Self, SettingsError> {
+        match settings_dir().map(|dir| dir.join(SETTINGS_FILE_NAME)) {
+            Some(path) if path.exists() => Self::load_from(path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: PathBuf) -> Result<Self, SettingsError> {
+        let error = |message: String| SettingsError {
+            path: path.clone(),
+            message,
+        };
+        let content = fs::read_to_string(&path).map_err(|e| error(e.to_string()))?;
+        Self::parse(&content).map_err(error)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let file: SettingsFile = toml::from_str(content).map_err(|e| e.to_string())?;
+        let mut settings = Self {
+            config_files: file
+                .paths
+                .config_files
+                .iter()
+                .map(|p| expand_tilde(p))
+                .collect(),
+            config_dirs: file
+                .paths
+                .config_dirs
+                .iter()
+                .map(|p| expand_tilde(p))
+                .collect(),
+            ..Self::default()
+        };
+
+        let launcher = &mut settings.launcher;
+        if let Some(command) = file.launcher.command {
+            if command
+                .first()
+                .is_none_or(|program| program.trim().is_empty())
+            {
+                return Err("launcher.command must start with a program name".to_string());
+            }
+            launcher.command = command;
+            launcher.kind = LauncherKind::Custom;
+        }
+        if let Some(kind) = file.launcher.kind {
+            launcher.kind = kind.parse().map_err(|e| format!("launcher.kind: {}", e))?;
+        }
+        if let Some(mode) = file.launcher.tmux {
+            launcher.tmux_mode = mode.parse().map_err(|e| format!("launcher.tmux: {}", e))?;
+        }
+        for rule in file.launcher.hosts {
+            let kind = rule
+                .kind
+                .parse()
+                .map_err(|e| format!("launcher.hosts '{}': {}", rule.pattern, e))?;
+            launcher.hosts.push((rule.pattern, kind));
+        }
+
+        let keys = &mut settings.keys;
+        for (name, names, bindings) in [
+            ("quit", file.keys.quit, &mut keys.quit),
+            ("search", file.keys.search, &mut keys.search),
+            (
+                "global_search",
+                file.keys.global_search,
+                &mut keys.global_search,
+            ),
+            ("history", file.keys.history, &mut keys.history),
+            ("favourite", file.keys.favourite, &mut keys.favourite),
+            ("tag_filter", file.keys.tag_filter, &mut keys.tag_filter),
+            ("add_host", file.keys.add_host, &mut keys.add_host),
+            ("edit_host", file.keys.edit_host, &mut keys.edit_host),
+            ("delete_host", file.keys.delete_host, &mut keys.delete_host),
+            ("move_host", file.keys.move_host, &mut keys.move_host),
+            ("copy_host", file.keys.copy_host, &mut keys.copy_host),
+            ("rename_host", file.keys.rename_host, &mut keys.rename_host),
+            ("mark", file.keys.mark, &mut keys.mark),
+            ("bulk_edit", file.keys.bulk_edit, &mut keys.bulk_edit),
+            ("undo", file.keys.undo, &mut keys.undo),
+            ("redo", file.keys.redo, &mut keys.redo),
+            ("backups", file.keys.backups, &mut keys.backups),
+            ("connect", file.keys.connect, &mut keys.connect),
+            ("back", file.keys.back, &mut keys.back),
+            ("up", file.keys.up, &mut keys.up),
+            ("down", file.keys.down, &mut keys.down),
+        ] {
+            if let Some(names) = names {
+                *bindings = names
+                    .iter()
+                    .map(|key| key.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("keys.{}: {}", name, e))?;
+            }
+        }
+
+        for (name, value, color) in [
+            (
+                "highlight",
+                file.theme.highlight,
+                &mut settings.theme.highlight,
+            ),
+            ("accent", file.theme.accent, &mut settings.theme.accent),
+        ] {
+            if let Some(value) = value {
+                *color = Color::from_str(&value)
+                    .map_err(|_| format!("theme.{}: unknown colour '{}'", name, value))?;
+            }
+        }
+
+        let production = &mut settings.production;
+        if let Some(tags) = file.production.tags {
+            production.tags = tags;
+        }
+        if let Some(confirm) = file.production.confirm {
+            production.confirm = confirm;
+        }
+        if let Some(value) = file.production.colour {
+            production.colour = Color::from_str(&value)
+                .map_err(|_| format!("production.colour: unknown colour '{}'", value))?;
+        }
+        if let Some(badge) = file.production.badge {
+            production.badge = badge;
+        }
+        for rule in file.production.hosts {
+            if rule.pattern.trim().is_empty() {
+                return Err("production.hosts: empty pattern".to_string());
+            }
+            production
+                .hosts
+                .push((rule.pattern, rule.confirm.unwrap_or(true)));
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Which hosts are production and how they are guarded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductionSettings {
+    /// Hosts with any of these tags are production.
+    pub tags: Vec<String>,
+    /// Whether connecting to a tagged host asks to type its alias first.
+    pub confirm: bool,
+    pub colour: Color,
+    /// Label shown next to production hosts.
+    pub badge: String,
+    /// Alias or HostName patterns of production hosts, with whether they ask
+    /// for confirmation. The first matching pattern wins over tags.
+    pub hosts: Vec<(String, bool)>,
+}
+
+impl Default for ProductionSettings {
+    fn default() -> Self {
+        Self {
+            tags: vec!["prod".to_string(), "production".to_string()],
+            confirm: true,
+            colour: Color::Red,
+            badge: "PROD".to_string(),
+            hosts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LauncherSettings {
+    pub kind: LauncherKind,
+    /// Program and arguments for the custom launcher. `{ssh}` is replaced by
+    /// the full ssh command line, `{alias}` by the host and `{config}` by the
+    /// config file.
+    pub command: Vec<String>,
+    /// What tmux opens when the TUI already runs inside tmux.
+    pub tmux_mode: TmuxMode,
+    /// Host patterns with the launcher to use for them, first match wins.
+    pub hosts: Vec<(String, LauncherKind)>,
+}
+
+impl Default for LauncherSettings {
+    fn default() -> Self {
+        Self {
+            kind: LauncherKind::Auto,
+            command: ["wt", "-w", "0", "new-tab", "cmd", "/k", "{ssh}"]
+                .map(String::from)
+                .to_vec(),
+            tmux_mode: TmuxMode::Window,
+            hosts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Selected row in the lists.
+    pub highlight: Color,
+    /// Labels in the details pane.
+    pub accent: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            highlight: Color::Yellow,
+            accent: Color::Cyan,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    Search,
+    GlobalSearch,
+    History,
+    /// Star or unstar the selected host.
+    Favourite,
+    /// List only hosts with the next tag.
+    TagFilter,
+    /// Open the host form for a new host.
+    AddHost,
+    /// Open the host form for the selected host.
+    EditHost,
+    /// Remove the selected host from its config file.
+    DeleteHost,
+    /// Move the selected host to another config file.
+    MoveHost,
+    /// Copy the selected host to another config file.
+    CopyHost,
+    /// Rename the selected host and the references to it.
+    RenameHost,
+    /// Mark or unmark the selected host for a bulk edit.
+    Mark,
+    /// Set or remove an option on the marked hosts.
+    BulkEdit,
+    /// Revert the last edit to the config files.
+    Undo,
+    /// Make the last undone edit again.
+    Redo,
+    /// List the backups of the config files.
+    Backups,
+    Connect,
+    Back,
+    Up,
+    Down,
+}
+
+/// Whether `key` types a character into a text field. Ctrl or Alt alone make
+/// a shortcut, but AltGr, which terminals report as Ctrl and Alt together,
+/// types characters such as `@` or `|` on many layouts.
+pub fn is_text_input(key: &KeyEvent) -> bool {
+    let shortcut = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+    matches!(key.code, KeyCode::Char(_))
+        && shortcut != KeyModifiers::CONTROL
+        && shortcut != KeyModifiers::ALT
+}
+
+/// A key with optional Ctrl/Alt, written like `q`, `Enter` or `Ctrl+f`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is already reflected in the character, so only compare the
+        // modifiers that change a key's meaning.
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        key.code == self.code && key.modifiers & relevant == self.modifiers & relevant
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl+") || lower.starts_with("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest[5..];
+            } else if lower.starts_with("alt+") || lower.starts_with("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest[4..];
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                other => match other.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", name)),
+                },
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    pub quit: Vec<KeyBinding>,
+    pub search: Vec<KeyBinding>,
+    pub global_search: Vec<KeyBinding>,
+    pub history: Vec<KeyBinding>,
+    pub favourite: Vec<KeyBinding>,
+    pub tag_filter: Vec<KeyBinding>,
+    pub add_host: Vec<KeyBinding>,
+    pub edit_host: Vec<KeyBinding>,
+    pub delete_host: Vec<KeyBinding>,
+    pub move_host: Vec<KeyBinding>,
+    pub copy_host: Vec<KeyBinding>,
+    pub rename_host: Vec<KeyBinding>,
+    pub mark: Vec<KeyBinding>,
+    pub bulk_edit: Vec<KeyBinding>,
+    pub undo: Vec<KeyBinding>,
+    pub redo: Vec<KeyBinding>,
+    pub backups: Vec<KeyBinding>,
+    pub connect: Vec<KeyBinding>,
+    pub back: Vec<KeyBinding>,
+    pub up: Vec<KeyBinding>,
+    pub down: Vec<KeyBinding>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            quit: vec![
+                KeyBinding::new(KeyCode::Char('q')),
+                KeyBinding::new(KeyCode::Char('Q')),
+            ],
+            search: vec![KeyBinding::new(KeyCode::Char('/'))],
+            global_search: vec![KeyBinding::new(KeyCode::Tab)],
+            history: vec![KeyBinding {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::CONTROL,
+            }],
+            favourite: vec![KeyBinding::new(KeyCode::Char('*'))],
+            tag_filter: vec![KeyBinding::new(KeyCode::Char('t'))],
+            add_host: vec![KeyBinding::new(KeyCode::Char('a'))],
+            edit_host: vec![KeyBinding::new(KeyCode::Char('e'))],
+            delete_host: vec![KeyBinding::new(KeyCode::Char('d'))],
+            move_host: vec![KeyBinding::new(KeyCode::Char('m'))],
+            copy_host: vec![KeyBinding::new(KeyCode::Char('y'))],
+            rename_host: vec![KeyBinding::new(KeyCode::Char('r'))],
+            mark: vec![KeyBinding::new(KeyCode::Char(' '))],
+            bulk_edit: vec![KeyBinding::new(KeyCode::Char('o'))],
+            undo: vec![KeyBinding {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+            }],
+            redo: vec![KeyBinding {
+                code: KeyCode::Char('y'),
+                modifiers: KeyModifiers::CONTROL,
+            }],
+            backups: vec![KeyBinding {
+                code: KeyCode::Char('b'),
+                modifiers: KeyModifiers::CONTROL,
+            }],
+            connect: vec![KeyBinding::new(KeyCode::Enter)],
+            back: vec![KeyBinding::new(KeyCode::Esc)],
+            up: vec![KeyBinding::new(KeyCode::Up)],
+            down: vec![KeyBinding::new(KeyCode::Down)],
+        }
+    }
+}
+
+impl KeyMap {
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        [
+            (&self.quit, Action::Quit),
+            (&self.search, Action::Search),
+            (&self.global_search, Action::GlobalSearch),
+            (&self.history, Action::History),
+            (&self.favourite, Action::Favourite),
+            (&self.tag_filter, Action::TagFilter),
+            (&self.add_host, Action::AddHost),
+            (&self.edit_host, Action::EditHost),
+            (&self.delete_host, Action::DeleteHost),
+            (&self.move_host, Action::MoveHost),
+            (&self.copy_host, Action::CopyHost),
+            (&self.rename_host, Action::RenameHost),
+            (&self.mark, Action::Mark),
+            (&self.bulk_edit, Action::BulkEdit),
+            (&self.undo, Action::Undo),
+            (&self.redo, Action::Redo),
+            (&self.backups, Action::Backups),
+            (&self.connect, Action::Connect),
+            (&self.back, Action::Back),
+            (&self.up, Action::Up),
+            (&self.down, Action::Down),
+        ]
+        .into_iter()
+        .find(|(bindings, _)| bindings.iter().any(|binding| binding.matches(key)))
+        .map(|(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::parse(
+            r##"
+            [paths]
+            config_dirs = ["/etc/team/ssh.d"]
+
+            [launcher]
+            command = ["kitty", "@", "launch", "sh", "-c", "{ssh}"]
+            tmux = "split"
+
+            [[launcher.hosts]]
+            pattern = "*.prod"
+            kind = "tmux"
+
+            [keys]
+            quit = ["Ctrl+c"]
+            connect = ["Enter", "l"]
+
+            [theme]
+            highlight = "#ff8800"
+
+            [production]
+            tags = ["live"]
+            badge = "LIVE"
+
+            [[production.hosts]]
+            pattern = "*.prod"
+
+            [[production.hosts]]
+            pattern = "*.staging"
+            confirm = false
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(settings.config_dirs, vec![PathBuf::from("/etc/team/ssh.d")]);
+        assert_eq!(settings.launcher.command[0], "kitty");
+        assert_eq!(settings.launcher.kind, LauncherKind::Custom);
+        assert_eq!(settings.launcher.tmux_mode, TmuxMode::Split);
+        assert_eq!(
+            settings.launcher.hosts,
+            vec![("*.prod".to_string(), LauncherKind::Tmux)]
+        );
+        assert_eq!(settings.theme.highlight, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(settings.theme.accent, Color::Cyan);
+        assert_eq!(settings.production.tags, vec!["live"]);
+        assert_eq!(settings.production.badge, "LIVE");
+        assert_eq!(settings.production.colour, Color::Red);
+        assert_eq!(
+            settings.production.hosts,
+            vec![
+                ("*.prod".to_string(), true),
+                ("*.staging".to_string(), false)
+            ]
+        );
+
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let plain_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        let l = KeyEvent::new(KeyCode::Char('l'), KeyModifiers::NONE);
+        let slash = KeyEvent::new(KeyCode::Char('/'), KeyModifiers::NONE);
+        assert_eq!(settings.keys.action_for(&ctrl_c), Some(Action::Quit));
+        assert_eq!(settings.keys.action_for(&plain_c), None);
+        assert_eq!(settings.keys.action_for(&l), Some(Action::Connect));
+        assert_eq!(settings.keys.action_for(&slash), Some(Action::Search));
+    }
+
+    #[test]
+    fn test_parse_settings_errors() {
+        let error = |content: &str| Settings::parse(content).unwrap_err();
+
+        assert!(error("[keys]\nquit = [\"Entr\"]").contains("keys.quit: unknown key 'Entr'"));
+        assert!(error("[theme]\naccent = \"blurple\"").contains("theme.accent"));
+        assert!(error("[launcher]\ncommand = []").contains("launcher.command"));
+        assert!(error("[launcher]\nkind = \"xterm\"").contains("launcher.kind"));
+        assert!(error("[production]\ncolour = \"rouge\"").contains("production.colour"));
+        assert!(error("[paths]\nconfig_folder = \"x\"").contains("unknown field"));
+        assert!(Settings::parse("").is_ok());
+    }
+
+    #[test]
+    fn test_is_text_input() {
+        let key = |c, modifiers| KeyEvent::new(KeyCode::Char(c), modifiers);
+        assert!(is_text_input(&key('a', KeyModifiers::NONE)));
+        assert!(is_text_input(&key('A', KeyModifiers::SHIFT)));
+        assert!(is_text_input(&key(
+            '@',
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        )));
+        assert!(!is_text_input(&key('z', KeyModifiers::CONTROL)));
+        assert!(!is_text_input(&key('b', KeyModifiers::ALT)));
+        assert!(!is_text_input(&KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE
+        )));
+    }
+}
@@ -0,0 +1,146 @@
+//! Application state kept between runs in `state.toml` next to the
+//! connection history, so the ssh config files themselves are never touched.
+
+use super::backup::write_file;
+use super::history::data_dir;
+use super::parser::SshHost;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+
+pub const STATE_FILE_NAME: &str = "state.toml";
+
+/// A host as ssh is asked to connect to it: an alias and the `-F` config
+/// file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostRef {
+    pub alias: String,
+    pub config_file: PathBuf,
+}
+
+impl HostRef {
+    pub fn of(host: &SshHost) -> Self {
+        Self {
+            alias: host.alias.clone(),
+            config_file: host.config_file.clone(),
+        }
+    }
+
+    fn is(&self, host: &SshHost) -> bool {
+        self.alias == host.alias && self.config_file == host.config_file
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StateFile {
+    favourites: Vec<HostRef>,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    /// The state file, `None` when state is not persisted.
+    path: Option<PathBuf>,
+    /// Starred hosts in the order they were starred.
+    pub favourites: Vec<HostRef>,
+}
+
+impl State {
+    /// Loads the state from the data directory. A missing file is an empty
+    /// state.
+    pub fn load() -> Result<Self, String> {
+        match data_dir() {
+            Some(dir) => Self::load_from(dir.join(STATE_FILE_NAME)),
+            None => Ok(State::default()),
+        }
+    }
+
+    pub fn load_from(path: PathBuf) -> Result<Self, String> {
+        let file: StateFile = match fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => StateFile::default(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Ok(State {
+            path: Some(path),
+            favourites: file.favourites,
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let file = StateFile {
+            favourites: self.favourites.clone(),
+        };
+        let content =
+            toml::to_string(&file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_file(path, &content)
+    }
+
+    pub fn is_favourite(&self, host: &SshHost) -> bool {
+        self.favourites.iter().any(|favourite| favourite.is(host))
+    }
+
+    /// Stars or unstars `host` and saves the state. Returns whether the host
+    /// is now a favourite.
+    pub fn toggle_favourite(&mut self, host: &SshHost) -> io::Result<bool> {
+        let starred = if self.is_favourite(host) {
+            self.favourites.retain(|favourite| !favourite.is(host));
+            false
+        } else {
+            self.favourites.push(HostRef::of(host));
+            true
+        };
+        self.save()?;
+        Ok(starred)
+    }
+
+    /// Points the favourite for `host`, if it is starred, at `new` after the
+    /// host was renamed or moved, and saves the state.
+    pub fn replace_favourite(&mut self, host: &SshHost, new: HostRef) -> io::Result<()> {
+        let Some(favourite) = self
+            .favourites
+            .iter_mut()
+            .find(|favourite| favourite.is(host))
+        else {
+            return Ok(());
+        };
+        *favourite = new;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_and_reload() {
+        let dir = std::env::temp_dir().join("sshtui_state_test");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(STATE_FILE_NAME);
+
+        let web = SshHost::new("web".to_string(), "/etc/ssh/work".into(), 1);
+        let db = SshHost::new("db".to_string(), "/etc/ssh/work".into(), 5);
+        let mut state = State::load_from(path.clone()).unwrap();
+        assert!(state.toggle_favourite(&web).unwrap());
+        assert!(state.toggle_favourite(&db).unwrap());
+        assert!(!state.toggle_favourite(&web).unwrap());
+
+        let reloaded = State::load_from(path.clone()).unwrap();
+        assert_eq!(reloaded.favourites, vec![HostRef::of(&db)]);
+        assert!(reloaded.is_favourite(&db));
+        assert!(!reloaded.is_favourite(&web));
+
+        fs::write(&path, "favourites = 3").unwrap();
+        assert!(State::load_from(path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
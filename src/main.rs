@@ -1,17 +1,232 @@
 use ratatui::{
     crossterm::event::{self, KeyCode, KeyEventKind},
+    layout::Flex,
     prelude::*,
     widgets::*,
     DefaultTerminal,
 };
-use std::io::{self, Result};
+use std::{
+    env,
+    io::{self, Result},
+    process,
+};
 
 mod app;
 
+use app::diff::Change;
+use app::locations::{ConfigLocations, USAGE};
+use app::safety::Protection;
+use app::settings::{is_text_input, Action, Settings, Theme};
+
 // In main.rs
 
-fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
-    let mut app = app::App::new();
+/// Describes the selected host for the details pane: the connection ssh will
+/// make, the jump hosts it goes through, forwards, whether it is production
+/// and where it is declared.
+fn host_details(
+    host: &app::parser::SshHost,
+    config: &app::resolve::EffectiveConfig,
+    protection: Option<Protection>,
+    settings: &Settings,
+) -> Vec<Line<'static>> {
+    let theme = &settings.theme;
+    let label =
+        |name: &str| Span::styled(format!("{:<13}", name), Style::default().fg(theme.accent));
+    let mut lines = vec![
+        Line::from(Span::styled(host.alias.clone(), Style::default().add_modifier(Modifier::BOLD))),
+        Line::default(),
+    ];
+
+    for keyword in ["HostName", "User", "Port"] {
+        let Some(option) = config
+            .options
+            .iter()
+            .find(|option| option.keyword.eq_ignore_ascii_case(keyword))
+        else {
+            continue;
+        };
+        let mut spans = vec![label(keyword), Span::raw(option.value.clone())];
+        if option.origin.is_none() {
+            spans.push(Span::styled(" (default)", Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    for identity in config.get_all("identityfile") {
+        lines.push(Line::from(vec![label("IdentityFile"), Span::raw(identity.to_string())]));
+    }
+
+    if let Some(jump) = config.get("proxyjump").filter(|jump| !jump.eq_ignore_ascii_case("none")) {
+        let mut chain: Vec<&str> = jump.split(',').map(str::trim).collect();
+        chain.push(&host.alias);
+        lines.push(Line::from(vec![label("ProxyJump"), Span::raw(chain.join(" → "))]));
+    }
+
+    for (keyword, name) in [
+        ("localforward", "LocalForward"),
+        ("remoteforward", "RemoteForward"),
+        ("dynamicforward", "DynamicFwd"),
+    ] {
+        for forward in config.get_all(keyword) {
+            lines.push(Line::from(vec![label(name), Span::raw(forward.to_string())]));
+        }
+    }
+
+    lines.push(Line::default());
+    if let Some(protection) = protection {
+        let guard = match protection {
+            Protection::Confirm => "production, asks to confirm connecting",
+            Protection::Marked => "production",
+        };
+        lines.push(Line::from(vec![
+            label("Environment"),
+            Span::styled(guard, Style::default().fg(settings.production.colour)),
+        ]));
+    }
+    if let Some(group) = &host.group {
+        lines.push(Line::from(vec![label("Section"), Span::raw(group.clone())]));
+    }
+    if !host.tags.is_empty() {
+        lines.push(Line::from(vec![label("Tags"), Span::raw(host.tags.join(", "))]));
+    }
+    lines.push(Line::from(vec![
+        label("Source"),
+        Span::raw(format!("{}:{}", host.source_file.display(), host.line)),
+    ]));
+
+    for note in &config.notes {
+        lines.push(Line::from(Span::styled(note.clone(), Style::default().fg(Color::DarkGray))));
+    }
+    lines
+}
+
+/// The rows of the host form, the focused one marked and followed by a
+/// cursor.
+fn host_form(form: &app::editor::HostForm, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for row in 0..form.rows() {
+        let focused = row == form.focus;
+        let mut value = match row {
+            0 => form.file.display().to_string(),
+            row => form.values[row - 1].clone(),
+        };
+        if focused && row == 0 && !form.files.is_empty() {
+            value = format!("◂ {} ▸", value);
+        } else if focused && row > 0 {
+            value.push('_');
+        }
+        let label_style = if focused {
+            Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.accent)
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:<13}", form.label(row)), label_style),
+            Span::raw(value),
+        ]));
+    }
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled(
+        "Tab/↓: next field, ←/→: file, Enter: save, Esc: cancel. \
+         Options are written as 'Keyword value'.",
+        Style::default().fg(Color::DarkGray),
+    )));
+    lines
+}
+
+/// The option and value of a bulk edit, or the diff of each file once it is
+/// previewed, at most `height` lines of it from the scroll position.
+fn bulk_edit(bulk: &app::editor::BulkEdit, theme: &Theme, height: usize) -> Vec<Line<'static>> {
+    let hint =
+        |text: &'static str| Line::from(Span::styled(text, Style::default().fg(Color::DarkGray)));
+    let Some(previews) = &bulk.preview else {
+        let aliases: Vec<&str> = bulk.hosts.iter().map(|host| host.alias.as_str()).collect();
+        let mut lines = vec![
+            Line::from(format!("On {}", aliases.join(", "))),
+            Line::default(),
+        ];
+        for (label, value, focused) in [
+            ("Option", &bulk.keyword, !bulk.on_value),
+            ("Value", &bulk.value, bulk.on_value),
+        ] {
+            let (label_style, cursor) = if focused {
+                (
+                    Style::default()
+                        .fg(theme.highlight)
+                        .add_modifier(Modifier::BOLD),
+                    "_",
+                )
+            } else {
+                (Style::default().fg(theme.accent), "")
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<13}", label), label_style),
+                Span::raw(format!("{}{}", value, cursor)),
+            ]));
+        }
+        lines.push(Line::default());
+        lines.push(hint(
+            "Tab: next field, Enter: preview, Esc: cancel. An empty value removes the option.",
+        ));
+        return lines;
+    };
+
+    let mut rows = Vec::new();
+    for preview in previews {
+        rows.push(Line::from(Span::styled(
+            preview.path.display().to_string(),
+            Style::default()
+                .fg(theme.accent)
+                .add_modifier(Modifier::BOLD),
+        )));
+        for line in &preview.lines {
+            let (sign, style) = match line.change {
+                Change::Same => (' ', Style::default().fg(Color::DarkGray)),
+                Change::Removed => ('-', Style::default().fg(Color::Red)),
+                Change::Added => ('+', Style::default().fg(Color::Green)),
+            };
+            rows.push(Line::from(Span::styled(
+                format!("{}{:>5} │ {}", sign, line.line, line.text),
+                style,
+            )));
+        }
+    }
+    let mut lines: Vec<Line> = rows.into_iter().skip(bulk.scroll).take(height).collect();
+    lines.push(Line::default());
+    lines.push(hint("↑/↓: scroll, Enter: write, Esc: back to the option"));
+    lines
+}
+
+/// Draws `lines` in a bordered box centered over the screen.
+fn render_popup(
+    frame: &mut Frame,
+    title: &str,
+    lines: Vec<Line<'static>>,
+    colour: Color,
+    width: u16,
+) {
+    let height = lines.len() as u16 + 2;
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::horizontal([Constraint::Percentage(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let popup = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+        Block::bordered()
+            .title(title.to_string())
+            .border_style(Style::default().fg(colour)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+fn run_render(
+    mut terminal: DefaultTerminal,
+    locations: ConfigLocations,
+    settings: Settings,
+) -> Result<()> {
+    let mut app = app::App::new(locations, settings);
 
     let mut startup_phase = true;
     let mut initial_event_ignored = 0;
@@ -20,8 +235,10 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
     loop {
         // Update scroll state before drawing
         let content_length = match app.mode {
-            app::AppMode::FileSelection => app.filtered_files.len(),
-            app::AppMode::HostSelection | app::AppMode::Search => app.filtered_hosts.len(),
+            app::AppMode::FileSelection => app.file_rows(),
+            app::AppMode::History => app.filtered_history.len(),
+            app::AppMode::Backups => app.backups.entries().len(),
+            _ => app.host_rows.len(),
         };
 
         app.vertical_scroll_state = app
@@ -42,10 +259,31 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
             let current_mode = app.mode.clone();
             let search_query = app.search_query.clone();
             let status_message = app.status_message.clone();
+            let details = app
+                .selected_host()
+                .zip(app.selected_effective_config())
+                .map(|(host, config)| {
+                    host_details(host, &config, app.protection(host), &app.settings)
+                });
 
             let list = List::new(items)
                 .block(Block::default().title(list_title).borders(Borders::ALL));
 
+            // Hosts get a details pane to the right of the list.
+            let list_area = match details {
+                Some(details) => {
+                    let [list_area, details_area] =
+                        Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                            .areas(list_area);
+                    let details_widget = Paragraph::new(details)
+                        .wrap(Wrap { trim: false })
+                        .block(Block::bordered().title("Details"));
+                    frame.render_widget(details_widget, details_area);
+                    list_area
+                }
+                None => list_area,
+            };
+
             // 2. Now, perform all the mutable operations on 'app'
             //    in a separate, subsequent action.
             frame.render_stateful_widget(list, list_area, &mut app.state);
@@ -69,7 +307,9 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
                     } else {
                         (
                             Style::default(),
-                            "Type to filter files, Enter to select".to_string()
+                            "Type to filter files, Enter to select, Tab to search all hosts, \
+                             Ctrl+r for history"
+                                .to_string()
                         )
                     }
                 }
@@ -85,6 +325,24 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
                         format!("Search: {}_", search_query)
                     )
                 }
+                app::AppMode::GlobalSearch => {
+                    (
+                        Style::default().fg(Color::Yellow),
+                        format!("Search all files: {}_", search_query)
+                    )
+                }
+                app::AppMode::History => {
+                    (
+                        Style::default().fg(Color::Yellow),
+                        format!("Filter history: {}_", search_query)
+                    )
+                }
+                app::AppMode::Backups => {
+                    (
+                        Style::default(),
+                        "Enter to restore the selected backup, Esc to go back".to_string()
+                    )
+                }
             };
 
             let search_widget = Paragraph::new(search_text)
@@ -95,11 +353,21 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
             // Status area (use the cloned variables)
             let status = match current_mode {
                 app::AppMode::FileSelection => {
-                    format!("File Selection - q: quit, type to filter\n{}", &status_message)
+                    format!(
+                        "File Selection - q: quit, type to filter, Tab: search all files, \
+                         Ctrl+r: history, Ctrl+b: backups\n\
+                         Config: {}\n{}",
+                        app.locations.describe(),
+                        &status_message
+                    )
                 }
                 app::AppMode::HostSelection => {
                     format!(
-                        "Host Selection - q: quit, /: search, Enter: connect, Esc: back\n{}",
+                        "Host Selection - q: quit, /: search, Enter: connect or fold section, \
+                         *: favourite, t: filter by tag, a/e/d: add/edit/delete host, \
+                         m/y/r: move/copy/rename host, Space/o: mark hosts/bulk edit, \
+                         Ctrl+z/Ctrl+y: undo/redo, Ctrl+b: backups, Ctrl+r: history, \
+                         Esc: back\n{}",
                         &status_message
                     )
                 }
@@ -109,10 +377,122 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
                         &status_message
                     )
                 }
+                app::AppMode::GlobalSearch => {
+                    format!(
+                        "Global Search - ESC: back to files, Enter: connect\n{}",
+                        &status_message
+                    )
+                }
+                app::AppMode::History => {
+                    format!(
+                        "Connection History - type to filter, Enter: reconnect, ESC: back\n{}",
+                        &status_message
+                    )
+                }
+                app::AppMode::Backups => {
+                    format!(
+                        "Config Backups - Enter: restore, Ctrl+z/Ctrl+y: undo/redo, ESC: back\n{}",
+                        &status_message
+                    )
+                }
             };
 
             let debug_message = Paragraph::new(status).block(Block::bordered().title("Status"));
             frame.render_widget(debug_message, debug_area);
+
+            // Connections to production hosts wait for the alias to be typed.
+            if let Some(confirmation) = &app.confirmation {
+                let text = vec![
+                    Line::from(format!(
+                        "{} is a production host. Type its alias to connect:",
+                        confirmation.target.alias
+                    )),
+                    Line::default(),
+                    Line::from(Span::styled(
+                        format!("{}_", confirmation.input),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Line::from(Span::styled(
+                        "Enter: connect, Esc: cancel",
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ];
+                let colour = app.settings.production.colour;
+                render_popup(frame, "Confirm connection", text, colour, 60);
+            }
+            if let Some(form) = &app.form {
+                let title = match &form.editing {
+                    Some(host) => format!("Edit {}", host.alias),
+                    None => "New host".to_string(),
+                };
+                let lines = host_form(form, &app.settings.theme);
+                render_popup(frame, &title, lines, app.settings.theme.accent, 70);
+            }
+            if let Some(host) = &app.deletion {
+                let text = vec![
+                    Line::from(format!(
+                        "Delete {} from {}?",
+                        host.alias,
+                        host.source_file.display()
+                    )),
+                    Line::from(Span::styled(
+                        "y: delete, any other key: cancel",
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ];
+                render_popup(frame, "Delete host", text, Color::Red, 60);
+            }
+            if let Some(transfer) = &app.transfer {
+                let verb = if transfer.copy { "Copy" } else { "Move" };
+                let mut text = vec![Line::from(format!(
+                    "{} {} from {} to:",
+                    verb,
+                    transfer.host.alias,
+                    transfer.host.source_file.display()
+                ))];
+                for (i, path) in transfer.files.iter().enumerate() {
+                    let style = if i == transfer.selected {
+                        Style::default()
+                            .fg(app.settings.theme.highlight)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    text.push(Line::from(Span::styled(
+                        format!("  {}", path.display()),
+                        style,
+                    )));
+                }
+                text.push(Line::from(Span::styled(
+                    "Up/Down: pick a file, Enter: confirm, Esc: cancel",
+                    Style::default().fg(Color::DarkGray),
+                )));
+                let title = format!("{} host", verb);
+                render_popup(frame, &title, text, app.settings.theme.accent, 70);
+            }
+            if let Some(bulk) = &app.bulk {
+                let height = frame.area().height.saturating_sub(6) as usize;
+                let lines = bulk_edit(bulk, &app.settings.theme, height);
+                render_popup(frame, &bulk.description(), lines, app.settings.theme.accent, 80);
+            }
+            if let Some(rename) = &app.renaming {
+                let text = vec![
+                    Line::from(format!(
+                        "New alias for {}, ProxyJump and ProxyCommand references follow:",
+                        rename.host.alias
+                    )),
+                    Line::default(),
+                    Line::from(Span::styled(
+                        format!("{}_", rename.input),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    )),
+                    Line::from(Span::styled(
+                        "Enter: rename, Esc: cancel",
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ];
+                render_popup(frame, "Rename host", text, app.settings.theme.accent, 70);
+            }
         })?;
 
         if let event::Event::Key(key) = event::read()? {
@@ -122,62 +502,226 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
                     startup_phase = false;
                 }
             } else if key.kind == KeyEventKind::Press {
+                let action = app.settings.keys.action_for(&key);
+                if app.confirmation.is_some() {
+                    match (action, key.code) {
+                        (_, KeyCode::Backspace) => app.erase_confirmation(),
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
+                            app.type_confirmation(c);
+                        }
+                        (Some(Action::Back), _) => app.cancel_confirmation(),
+                        (Some(Action::Connect), _) => {
+                            app.confirm_connection()?;
+                            terminal.clear()?;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(form) = app.form.as_mut() {
+                    match (action, key.code) {
+                        (_, KeyCode::Tab | KeyCode::Down) => form.next_row(),
+                        (_, KeyCode::BackTab | KeyCode::Up) => form.previous_row(),
+                        (_, KeyCode::Left) => form.cycle_file(false),
+                        (_, KeyCode::Right) => form.cycle_file(true),
+                        (_, KeyCode::Backspace) => form.erase(),
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
+                            form.type_char(c);
+                        }
+                        (Some(Action::Back), _) => app.cancel_form(),
+                        (Some(Action::Connect), _) => app.save_form(),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.deletion.is_some() {
+                    match key.code {
+                        KeyCode::Char('y') => app.delete_host(),
+                        _ => app.cancel_deletion(),
+                    }
+                    continue;
+                }
+                if let Some(transfer) = app.transfer.as_mut() {
+                    match (action, key.code) {
+                        (Some(Action::Up), _) => transfer.move_selection(false),
+                        (Some(Action::Down), _) => transfer.move_selection(true),
+                        (Some(Action::Back), _) => app.cancel_transfer(),
+                        (Some(Action::Connect), _) => app.confirm_transfer(),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(bulk) = app.bulk.as_mut() {
+                    match (action, key.code) {
+                        (Some(Action::Back), _) => app.cancel_bulk_edit(),
+                        (Some(Action::Connect), _) if bulk.preview.is_some() => {
+                            app.apply_bulk_edit();
+                        }
+                        (Some(Action::Connect), _) => app.preview_bulk_edit(),
+                        (_, KeyCode::Up) if bulk.preview.is_some() => bulk.scroll_preview(false),
+                        (_, KeyCode::Down) if bulk.preview.is_some() => bulk.scroll_preview(true),
+                        _ if bulk.preview.is_some() => {}
+                        (_, KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down) => {
+                            bulk.switch_field();
+                        }
+                        (_, KeyCode::Backspace) => bulk.erase(),
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
+                            bulk.type_char(c);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.renaming.is_some() {
+                    match (action, key.code) {
+                        (_, KeyCode::Backspace) => app.erase_rename(),
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
+                            app.type_rename(c);
+                        }
+                        (Some(Action::Back), _) => app.cancel_rename(),
+                        (Some(Action::Connect), _) => app.confirm_rename(),
+                        _ => {}
+                    }
+                    continue;
+                }
                 match app.mode {
-                    app::AppMode::FileSelection => match key.code {
-                        KeyCode::Char('q') | KeyCode::Char('Q') => {
+                    app::AppMode::FileSelection => match (action, key.code) {
+                        (Some(Action::Quit), _) => {
                             terminal.clear()?;
                             return Ok(());
                         }
-                        KeyCode::Up => app.move_up(),
-                        KeyCode::Down => app.move_down(),
-                        KeyCode::Enter => {
+                        (Some(Action::Up), _) => app.move_up(),
+                        (Some(Action::Down), _) => app.move_down(),
+                        (Some(Action::Connect), _) => {
                             app.load_hosts_from_selected_file();
                         }
-                        KeyCode::Backspace => {
+                        (Some(Action::GlobalSearch), _) => {
+                            app.enter_global_search();
+                        }
+                        (Some(Action::History), _) => {
+                            app.enter_history();
+                        }
+                        (Some(Action::Backups), _) => {
+                            app.enter_backups();
+                        }
+                        (Some(Action::Undo), _) => app.undo(),
+                        (Some(Action::Redo), _) => app.redo(),
+                        (_, KeyCode::Backspace) => {
                             app.remove_char_from_search();
                         }
-                        KeyCode::Char(c) => {
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
                             app.add_char_to_search(c);
                         }
                         _ => {}
                     },
-                    app::AppMode::HostSelection => match key.code {
-                        KeyCode::Char('q') | KeyCode::Char('Q') => {
+                    app::AppMode::HostSelection => match action {
+                        Some(Action::Quit) => {
                             terminal.clear()?;
                             return Ok(());
                         }
-                        KeyCode::Char('/') => {
+                        Some(Action::Search) => {
                             app.enter_search_mode();
                         }
-                        KeyCode::Up => app.move_up(),
-                        KeyCode::Down => app.move_down(),
-                        KeyCode::Enter => {
-                            app.tmux_session()?;
+                        Some(Action::Up) => app.move_up(),
+                        Some(Action::Down) => app.move_down(),
+                        // Enter on a section heading folds it instead.
+                        Some(Action::Connect) if !app.toggle_selected_group() => {
+                            app.connect()?;
                             terminal.clear()?;
                         }
-                        KeyCode::Esc => {
+                        Some(Action::Back) => {
                             app.back_to_file_selection();
                         }
+                        Some(Action::TagFilter) => {
+                            app.cycle_tag_filter();
+                        }
+                        Some(Action::History) => {
+                            app.enter_history();
+                        }
+                        Some(Action::Favourite) => {
+                            app.toggle_favourite();
+                        }
+                        Some(Action::AddHost) => {
+                            app.open_new_host_form();
+                        }
+                        Some(Action::EditHost) => {
+                            app.open_edit_form();
+                        }
+                        Some(Action::DeleteHost) => {
+                            app.request_deletion();
+                        }
+                        Some(Action::MoveHost) => {
+                            app.open_transfer(false);
+                        }
+                        Some(Action::CopyHost) => {
+                            app.open_transfer(true);
+                        }
+                        Some(Action::RenameHost) => {
+                            app.open_rename();
+                        }
+                        Some(Action::Mark) => app.toggle_mark(),
+                        Some(Action::BulkEdit) => {
+                            app.open_bulk_edit();
+                        }
+                        Some(Action::Undo) => app.undo(),
+                        Some(Action::Redo) => app.redo(),
+                        Some(Action::Backups) => {
+                            app.enter_backups();
+                        }
                         _ => {}
                     },
-                    app::AppMode::Search => match key.code {
-                        KeyCode::Esc => {
+                    // Characters are search text here, so apart from the
+                    // favourite key only bindings on other keys apply.
+                    app::AppMode::Search | app::AppMode::GlobalSearch => match (action, key.code) {
+                        (_, KeyCode::Backspace) => {
+                            app.remove_char_from_search();
+                        }
+                        (Some(Action::Favourite), _) => {
+                            app.toggle_favourite();
+                        }
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
+                            app.add_char_to_search(c);
+                        }
+                        (Some(Action::Back), _) => {
                             app.exit_search_mode();
                         }
-                        KeyCode::Enter => {
-                            if !app.filtered_hosts.is_empty() {
-                                app.tmux_session()?;
-                                terminal.clear()?;
-                            }
+                        (Some(Action::Connect), _) if !app.filtered_hosts.is_empty() => {
+                            app.connect()?;
+                            terminal.clear()?;
                         }
-                        KeyCode::Up => app.move_up(),
-                        KeyCode::Down => app.move_down(),
-                        KeyCode::Backspace => {
+                        (Some(Action::Up), _) => app.move_up(),
+                        (Some(Action::Down), _) => app.move_down(),
+                        _ => {}
+                    },
+                    app::AppMode::History => match (action, key.code) {
+                        (_, KeyCode::Backspace) => {
                             app.remove_char_from_search();
                         }
-                        KeyCode::Char(c) => {
+                        (_, KeyCode::Char(c)) if is_text_input(&key) => {
                             app.add_char_to_search(c);
                         }
+                        (Some(Action::Back), _) => {
+                            app.exit_history();
+                        }
+                        (Some(Action::Connect), _) if !app.filtered_history.is_empty() => {
+                            app.connect()?;
+                            terminal.clear()?;
+                        }
+                        (Some(Action::Up), _) => app.move_up(),
+                        (Some(Action::Down), _) => app.move_down(),
+                        _ => {}
+                    },
+                    app::AppMode::Backups => match action {
+                        Some(Action::Quit) => {
+                            terminal.clear()?;
+                            return Ok(());
+                        }
+                        Some(Action::Back) => app.exit_backups(),
+                        Some(Action::Connect) => app.restore_backup(),
+                        Some(Action::Undo) => app.undo(),
+                        Some(Action::Redo) => app.redo(),
+                        Some(Action::Up) => app.move_up(),
+                        Some(Action::Down) => app.move_down(),
                         _ => {}
                     },
                 }
@@ -186,9 +730,29 @@ fn run_render(mut terminal: DefaultTerminal) -> Result<()> {
     }
 }
 fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let locations = match ConfigLocations::resolve(args, |name| env::var(name).ok(), &settings) {
+        Ok(locations) => locations,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+
     let mut terminal = ratatui::init();
     terminal.clear()?;
-    let app_result = run_render(terminal);
+    let app_result = run_render(terminal, locations, settings);
     ratatui::restore();
     app_result
 }